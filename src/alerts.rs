@@ -0,0 +1,212 @@
+use std::{path::PathBuf, process::Command, thread, time::Duration};
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use log::{info, warn};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::json;
+use ureq::Agent;
+use uuid::Uuid;
+
+use crate::config::AlertConfig;
+
+/// The configured alerts, with their patterns compiled.
+pub struct Alerts {
+    rules: Vec<Rule>,
+    data_dir: PathBuf,
+    agent: Agent,
+}
+
+struct Rule {
+    config: AlertConfig,
+    patterns: Vec<Regex>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct AlertEvent {
+    pub alert: String,
+    /// The part of the transcript that set it off.
+    pub matched: String,
+    pub channel: String,
+    pub text: String,
+    pub audio: Uuid,
+    pub date: NaiveDateTime,
+}
+
+/// Compiles an alert's keywords and regexes.
+pub fn patterns(alert: &AlertConfig) -> Result<Vec<Regex>, regex::Error> {
+    let keywords = alert.keywords.iter().map(|keyword| {
+        let words = keyword
+            .split(|x: char| x.is_whitespace() || x == '-')
+            .filter(|x| !x.is_empty())
+            .map(regex::escape)
+            .collect::<Vec<_>>();
+        format!(r"(?i)\b{}\b", words.join(r"[\s\p{P}]*"))
+    });
+
+    keywords
+        .chain(alert.regexes.iter().cloned())
+        .map(|x| Regex::new(&x))
+        .collect()
+}
+
+impl Alerts {
+    pub fn new(configs: &[AlertConfig], data_dir: PathBuf) -> Result<Self> {
+        let rules = configs
+            .iter()
+            .map(|config| {
+                Ok(Rule {
+                    patterns: patterns(config)?,
+                    config: config.clone(),
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            rules,
+            data_dir,
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(10))
+                .build(),
+        })
+    }
+
+    /// Finds the alerts a transcript sets off, with the text that matched each.
+    fn matching(&self, channel: &str, text: &str) -> Vec<(&AlertConfig, String)> {
+        self.rules
+            .iter()
+            .filter(|x| {
+                x.config.channels.is_empty() || x.config.channels.iter().any(|x| x == channel)
+            })
+            .filter_map(|rule| {
+                let matched = rule.patterns.iter().find_map(|x| x.find(text))?;
+                Some((&rule.config, matched.as_str().to_owned()))
+            })
+            .collect()
+    }
+
+    /// Runs the actions of every alert the transcript sets off, returning them for the web UI.
+    pub fn check(
+        &self,
+        channel: &str,
+        text: &str,
+        audio: Uuid,
+        date: NaiveDateTime,
+    ) -> Vec<AlertEvent> {
+        let mut events = Vec::new();
+        for (config, matched) in self.matching(channel, text) {
+            let event = AlertEvent {
+                alert: config.name.clone(),
+                matched,
+                channel: channel.to_owned(),
+                text: text.to_owned(),
+                audio,
+                date,
+            };
+            info!(alert = config.name.as_str(), channel; "{text}");
+            self.fire(config, event.clone());
+            events.push(event);
+        }
+
+        events
+    }
+
+    /// Calls the webhook and runs the command in the background, so a slow one can't stall the radio.
+    fn fire(&self, config: &AlertConfig, event: AlertEvent) {
+        if let Some(webhook) = config.webhook.clone() {
+            let agent = self.agent.clone();
+            let event = event.clone();
+            thread::spawn(move || {
+                let response = agent
+                    .post(&webhook)
+                    .set("Content-Type", "application/json")
+                    .send_string(&json!(event).to_string());
+                if let Err(err) = response {
+                    warn!(alert = event.alert.as_str(); "Alert webhook failed: {err}");
+                }
+            });
+        }
+
+        if let [program, args @ ..] = config.command.as_slice() {
+            let audio = self
+                .data_dir
+                .join("audio")
+                .join(format!("{}.wav", event.audio));
+            let mut command = Command::new(program);
+            command
+                .args(args)
+                .env("ALERT_NAME", &event.alert)
+                .env("ALERT_MATCHED", &event.matched)
+                .env("ALERT_CHANNEL", &event.channel)
+                .env("ALERT_TEXT", &event.text)
+                .env("ALERT_AUDIO", audio)
+                .env("ALERT_DATE", event.date.to_string());
+
+            thread::spawn(move || match command.status() {
+                Ok(status) if !status.success() => {
+                    warn!(alert = event.alert.as_str(); "Alert command exited with {status}")
+                }
+                Err(err) => warn!(alert = event.alert.as_str(); "Alert command failed: {err}"),
+                Ok(_) => {}
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::Alerts;
+    use crate::config::AlertConfig;
+
+    fn alerts(keywords: &[&str], regexes: &[&str], channels: &[&str]) -> Alerts {
+        let config = AlertConfig {
+            name: "test".into(),
+            keywords: keywords.iter().map(|x| x.to_string()).collect(),
+            regexes: regexes.iter().map(|x| x.to_string()).collect(),
+            channels: channels.iter().map(|x| x.to_string()).collect(),
+            webhook: None,
+            command: Vec::new(),
+        };
+        Alerts::new(&[config], PathBuf::new()).unwrap()
+    }
+
+    fn matched(alerts: &Alerts, channel: &str, text: &str) -> Option<String> {
+        let matches = alerts.matching(channel, text);
+        matches.first().map(|(_, matched)| matched.clone())
+    }
+
+    #[test]
+    fn keywords_ignore_case_and_separators() {
+        let alerts = alerts(&["mayday", "pan-pan"], &[], &[]);
+        assert_eq!(
+            matched(&alerts, "16", "MAYDAY MAYDAY"),
+            Some("MAYDAY".into())
+        );
+        assert_eq!(
+            matched(&alerts, "16", "Pan pan, this is"),
+            Some("Pan pan".into())
+        );
+        assert_eq!(matched(&alerts, "16", "pan-pan"), Some("pan-pan".into()));
+        assert_eq!(matched(&alerts, "16", "Pan, pan."), Some("Pan, pan".into()));
+    }
+
+    #[test]
+    fn keywords_match_whole_words() {
+        let alerts = alerts(&["mayday"], &[], &[]);
+        assert_eq!(matched(&alerts, "16", "maydays"), None);
+        assert_eq!(matched(&alerts, "16", "routine traffic"), None);
+    }
+
+    #[test]
+    fn regexes_and_channels() {
+        let alerts = alerts(&[], &[r"(?i)sea ?breeze"], &["16"]);
+        assert_eq!(
+            matched(&alerts, "16", "Seabreeze, over"),
+            Some("Seabreeze".into())
+        );
+        assert_eq!(matched(&alerts, "9", "Seabreeze, over"), None);
+    }
+}
@@ -1,168 +1,790 @@
-use std::{fs::File, io::BufWriter, path::Path, time::Instant};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{self, File},
+    io::BufWriter,
+    mem,
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc},
+    thread,
+    time::Duration,
+};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use flume::Receiver;
 use hound::WavWriter;
-use rtlsdr::RTLSDRDevice;
+use log::{debug, error, info, warn};
 use uuid::Uuid;
 
 use crate::{
-    config::Config,
-    consts::{BUFFER_SIZE, WAVE_SAMPLE_RATE, WAVE_SPEC},
+    alerts::Alerts,
+    config::{Config, RadioConfig},
+    consts::{BUFFER_SIZE, IQ_CUTOFF_FREQ, SAMPLE_RATE, WAVE_SAMPLE_RATE, WAVE_SPEC},
+    device::{self, Device, Settings},
     filters::down_sample::DownSampleExt,
-    misc::date_time,
+    logger,
+    metrics::{Metrics, RadioMetrics},
+    reload::Reloader,
+    scanner::Scanner,
     signal::{
-        demodulate::Demodulator,
+        agc::Agc,
+        demodulate::{self, Demodulator},
+        spectrum::Spectrum,
+        tone::{Tone, ToneDetector},
         transcribe::{Transcriber, TRANSCRIBE_SAMPLE_RATE},
     },
+    sync::Upstream,
+    transcription::{Job, Transcriptions},
     web::{
-        self,
-        database::{self, Database},
-        UiMessage,
+        self, database::Database, ChannelMarker, LiveAudio, SpectrumFeed, SpectrumRow, UiMessage,
     },
 };
 
+/// Send each channel's signal level to the UI every this many buffers (~100ms).
+const LEVEL_INTERVAL: usize = 3;
+/// Send a row to the waterfall every this many buffers (~100ms).
+const SPECTRUM_INTERVAL: usize = 3;
+const SPECTRUM_BINS: usize = 1024;
+/// Fraction of the way the noise floor rises towards the current level each buffer (~30s time constant).
+const NOISE_FLOOR_RISE: f32 = 0.001;
+/// Reads with fewer IQ samples than this are dropped, as they're too short to demodulate.
+const MIN_SAMPLES: u64 = 64;
+/// How long to wait between attempts at reopening a failed device.
+const REOPEN_DELAY: Duration = Duration::from_secs(5);
+
+/// Runs one radio, recording the channels it receives.
 pub struct App {
     config: Config,
+    reload_rx: Receiver<Config>,
 
-    device: RTLSDRDevice,
+    /// This radio's entry in the config, matched up by name when it's reloaded.
+    radio: RadioConfig,
+    /// Indices of the channels this radio receives.
+    owned: Vec<usize>,
+    device: Device,
+    agc: Option<Agc>,
+    scanner: Option<Scanner>,
     demodulator: Demodulator,
-    recordings: Vec<Option<Message>>,
+    spectrum: Spectrum,
+    channels: Vec<Channel>,
+    buffers: usize,
     #[cfg(feature = "debug")]
-    debug: flume::Sender<Vec<num_complex::Complex<f32>>>,
+    debug: flume::Sender<crate::signal::debug::Frame>,
+
+    alerts: Arc<Alerts>,
+    transcriptions: Transcriptions,
+    metrics: Arc<Metrics>,
+    radio_metrics: Arc<RadioMetrics>,
+    live: LiveAudio,
+    spectrum_feed: SpectrumFeed,
+    web_tx: flume::Sender<UiMessage>,
+}
 
-    database: Database,
-    transcriber: Transcriber,
+/// What every radio shares: storage, transcription and the web UI.
+#[derive(Clone)]
+pub struct Shared {
+    transcriptions: Transcriptions,
+    metrics: Arc<Metrics>,
+    live: LiveAudio,
+    spectrum_feed: SpectrumFeed,
     web_tx: flume::Sender<UiMessage>,
 }
 
+struct Channel {
+    recording: Option<Message>,
+    demodulate: demodulate::ChannelState,
+    /// Seconds of audio since the signal was last above the squelch.
+    since_active: f32,
+    /// If the signal was above the squelch in the last buffer. Holds the scanner even before a
+    /// channel's tone has been decoded, which takes longer than a dwell.
+    open: bool,
+    /// Most recent audio, used to prepend what came before the squelch opened.
+    pre_roll: VecDeque<f32>,
+    /// Set when a recording was split, so the next one can link back to it.
+    previous: Option<Uuid>,
+    tone: ToneDetector,
+    /// Slowly tracks the quietest recent level, to help with setting the squelch.
+    noise_floor: f32,
+}
+
 struct Message {
     uuid: Uuid,
+    previous: Option<Uuid>,
+    path: PathBuf,
     wav: WavWriter<BufWriter<File>>,
     buffer: Vec<f32>,
+    /// Seconds of audio written while the signal was above the squelch.
+    signal: f32,
+    tone: Option<Tone>,
 }
 
-impl App {
-    pub fn new(config: Config) -> Result<Self> {
-        let device = rtlsdr::open(config.radio.device_index).unwrap();
-        let demodulator = Demodulator::empty();
-        let recordings = (0..config.channels.len()).map(|_| None).collect::<Vec<_>>();
-
+impl Shared {
+    /// Opens the database, loads the transcription model and starts the web server.
+    pub fn new(config: &Config, reloader: Reloader) -> Result<Self> {
         let database = Database::new(&config.misc.data_dir)?;
+        let upstream = config
+            .sync
+            .clone()
+            .map(|sync| Upstream::start(sync, database.clone(), config.misc.data_dir.clone()));
         let transcriber = Transcriber::new(&config.misc.transcribe_model)?;
-        let web_tx = web::start(&config.server, database.clone());
+        let metrics = Arc::new(Metrics::default());
+        let live = LiveAudio::default();
+        let spectrum_feed = SpectrumFeed::default();
+        let web_tx = web::start(
+            config,
+            database.clone(),
+            metrics.clone(),
+            reloader,
+            live.clone(),
+            spectrum_feed.clone(),
+        )?;
+        let transcriptions = Transcriptions::start(
+            transcriber,
+            database,
+            upstream,
+            metrics.clone(),
+            web_tx.clone(),
+        );
 
-        #[cfg(feature = "debug")]
-        let debug_tx = {
-            let (tx, rx) = flume::unbounded();
-            std::thread::spawn(move || crate::signal::debug::start(rx).unwrap());
-            tx
-        };
+        Ok(Self {
+            transcriptions,
+            metrics,
+            live,
+            spectrum_feed,
+            web_tx,
+        })
+    }
+}
+
+impl App {
+    /// Opens the device for `config.radios[radio]`.
+    pub fn new(
+        config: Config,
+        radio: usize,
+        shared: &Shared,
+        reloader: &Reloader,
+        #[cfg(feature = "debug")] debug: flume::Sender<crate::signal::debug::Frame>,
+    ) -> Result<Self> {
+        let demodulator = Demodulator::empty();
+        let owned = config.radio_channels(radio);
+        let radio = config.radios[radio].clone();
+        let scanner = scanner(&config, &radio, &owned);
+        let radio_metrics = shared.metrics.radio(&radio.name);
+        let mut device = Device::open(
+            device::find(&radio)?,
+            Settings {
+                center_freq: scanner.as_ref().map_or(radio.center_freq, |x| x.center()),
+                sample_rate: radio.sample_rate,
+                tuner_gain: radio.tuner_gain,
+            },
+            radio_metrics.clone(),
+        )
+        .with_context(|| format!("starting radio `{}`", radio.name))?;
+        let gain = supported_gain(&radio, device.tuner_gains());
+        if gain != radio.tuner_gain {
+            device.set_tuner_gain(gain);
+        }
+        let agc = radio
+            .agc
+            .then(|| Agc::new(device.tuner_gains().to_vec(), gain));
+        let channels = (0..config.channels.len())
+            .map(|_| Channel::new())
+            .collect::<Vec<_>>();
+
+        let alerts = Arc::new(Alerts::new(&config.alerts, config.misc.data_dir.clone())?);
+        let reload_rx = reloader.receiver();
 
         Ok(Self {
             config,
+            reload_rx,
+            radio,
+            owned,
             device,
+            agc,
+            scanner,
             demodulator,
-            recordings,
+            spectrum: Spectrum::new(SPECTRUM_BINS),
+            channels,
+            buffers: 0,
             #[cfg(feature = "debug")]
-            debug: debug_tx,
-            database,
-            transcriber,
-            web_tx,
+            debug,
+            alerts,
+            transcriptions: shared.transcriptions.clone(),
+            metrics: shared.metrics.clone(),
+            radio_metrics,
+            live: shared.live.clone(),
+            spectrum_feed: shared.spectrum_feed.clone(),
+            web_tx: shared.web_tx.clone(),
         })
     }
 
-    pub fn init_device(&mut self) {
-        let App { config, device, .. } = self;
+    pub fn name(&self) -> &str {
+        &self.radio.name
+    }
 
-        device.set_center_freq(config.radio.center_freq).unwrap();
-        device.set_sample_rate(config.radio.sample_rate).unwrap();
-        device.set_tuner_gain_mode(true).unwrap();
-        device.set_agc_mode(false).unwrap();
-        device.set_tuner_gain(config.radio.tuner_gain).unwrap();
-        device.reset_buffer().unwrap();
+    /// Runs the radio, reopening the device whenever it fails or stops sending samples.
+    pub fn run(&mut self) -> ! {
+        self.report_gain(self.device.tuner_gain());
+        loop {
+            if let Err(err) = self.process_samples() {
+                self.recover(err);
+            }
+        }
     }
 
-    pub fn process_samples(&mut self) {
-        let data = self.device.read_sync(BUFFER_SIZE).unwrap();
-        self.demodulator.replace(&data);
-        #[cfg(feature = "debug")]
-        self.debug.send(self.demodulator.iq().to_owned()).unwrap();
+    /// Ends the recordings in progress, then reopens the device, retrying until it's back.
+    fn recover(&mut self, err: anyhow::Error) {
+        let radio = self.radio.name.clone();
+        error!(radio = radio.as_str(); "Radio failed: {err:#}");
+        self.radio_metrics
+            .device_failures
+            .fetch_add(1, Ordering::Relaxed);
+        let _ = self.web_tx.send(UiMessage::Device {
+            radio: radio.clone(),
+            connected: false,
+            error: Some(format!("{err:#}")),
+        });
+
+        // Audio from after the gap shouldn't be joined onto what came before it
+        for idx in 0..self.channels.len() {
+            let channel = &mut self.channels[idx];
+            channel.pre_roll.clear();
+            channel.tone.reset();
+            channel.previous = None;
+            if let Some(message) = channel.recording.take() {
+                self.finalize_recording(idx, message);
+            }
+        }
 
+        while let Err(err) = self.device.reopen() {
+            warn!(radio = radio.as_str(); "Can't reopen the radio, retrying in {REOPEN_DELAY:?}: {err:#}");
+            thread::sleep(REOPEN_DELAY);
+        }
+
+        info!(radio = radio.as_str(); "Reopened the radio");
+        let _ = self.web_tx.send(UiMessage::Device {
+            radio,
+            connected: true,
+            error: None,
+        });
+    }
+
+    /// Reads and processes one buffer of samples.
+    /// Errors come from the device, which will need to be reopened.
+    fn process_samples(&mut self) -> Result<()> {
+        if let Some(config) = self.reload_rx.try_iter().last() {
+            if let Err(err) = self.apply_config(config) {
+                error!("Failed to apply config: {err:#}");
+            }
+        }
+
+        let samples = self.device.read()?;
+        let count = samples.data.len() as u64 / 2;
+        self.radio_metrics
+            .samples_read
+            .fetch_add(count, Ordering::Relaxed);
+        self.radio_metrics.samples_dropped.fetch_add(
+            BUFFER_SIZE.saturating_sub(samples.data.len()) as u64 / 2,
+            Ordering::Relaxed,
+        );
+        if count < MIN_SAMPLES {
+            self.radio_metrics
+                .samples_dropped
+                .fetch_add(count, Ordering::Relaxed);
+            return Ok(());
+        }
+        if let Some(gain) = self.agc.as_mut().and_then(|agc| agc.update(&samples.data)) {
+            self.device.set_tuner_gain(gain);
+            self.report_gain(gain);
+        }
+
+        // Buffers read before the last retune was done may still be queued
+        if samples.settling || samples.center_freq != self.center_freq() {
+            self.radio_metrics
+                .samples_dropped
+                .fetch_add(count, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        self.demodulator.replace(&samples.data);
+        self.radio_metrics
+            .buffers_processed
+            .fetch_add(1, Ordering::Relaxed);
+        self.buffers += 1;
+        let send_levels = self.buffers.is_multiple_of(LEVEL_INTERVAL);
+        let send_spectrum =
+            self.buffers.is_multiple_of(SPECTRUM_INTERVAL) && self.spectrum_feed.listening();
+        let mut markers = Vec::new();
+
+        let center_freq = self.center_freq();
         let mut finalize = Vec::new();
         for (idx, channel) in self.config.channels.iter().enumerate() {
-            let offset = channel.freq - self.config.radio.center_freq;
+            if !self.audible().contains(&idx) {
+                continue;
+            }
+
+            let offset = (channel.freq as i64 - center_freq as i64) as i32;
+            let state = &mut self.channels[idx];
 
             let rms = self.demodulator.rms(offset);
-            if rms < channel.squelch {
-                finalize.push(idx);
+            let audio =
+                self.demodulator
+                    .audio(&mut state.demodulate, offset, channel.gain, channel.mode);
+            let duration = audio.len() as f32 / WAVE_SAMPLE_RATE as f32;
+
+            state.update_noise_floor(rms);
+            let open = rms >= channel.squelch;
+            state.open = open;
+            if open || state.recording.is_some() {
+                state.tone.process(&audio);
+            } else {
+                state.tone.reset();
+            }
+
+            let detected = state.tone.tone();
+            let active = open
+                && channel
+                    .tone
+                    .is_none_or(|tone| detected.is_some_and(|x| tone.matches(&x)));
+            if send_levels {
+                let _ = self.web_tx.send(UiMessage::Level {
+                    idx: idx as u32,
+                    name: channel.name.to_owned(),
+                    uuid: state.recording.as_ref().map(|x| x.uuid),
+                    rms,
+                    noise_floor: state.noise_floor,
+                    open,
+                });
+            }
+
+            if send_spectrum || cfg!(feature = "debug") {
+                markers.push(ChannelMarker {
+                    idx: idx as u32,
+                    name: channel.name.to_owned(),
+                    freq: channel.freq,
+                    rms,
+                    squelch: channel.squelch,
+                    open,
+                });
+            }
+
+            if active {
+                state.since_active = 0.0;
+            } else {
+                state.since_active += duration;
+            }
+
+            if state.since_active > channel.hang_time {
+                state.push_pre_roll(&audio, channel.pre_roll);
+                state.previous = None;
+                if let Some(message) = state.recording.take() {
+                    finalize.push((idx, message));
+                }
                 continue;
             }
 
-            let message = self.recordings[idx].get_or_insert_with(|| {
-                self.web_tx
-                    .send(UiMessage::Receiving {
-                        idx: idx as u32,
-                        name: channel.name.to_owned(),
-                    })
-                    .unwrap();
-                Message::new(&self.config.misc.data_dir)
-            });
+            self.live.send(&channel.name, &audio);
+            let mut message = match state.recording.take() {
+                Some(message) => message,
+                None => {
+                    let pre_roll = state.pre_roll.drain(..).collect();
+                    match Message::start(
+                        &self.config.misc.data_dir,
+                        state.previous.take(),
+                        pre_roll,
+                    ) {
+                        Ok(message) => {
+                            let _ = self.web_tx.send(UiMessage::Receiving {
+                                idx: idx as u32,
+                                name: channel.name.to_owned(),
+                                uuid: message.uuid,
+                            });
+                            message
+                        }
+                        Err(err) => {
+                            error!(channel = channel.name.as_str(); "Can't start recording: {err:#}");
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            // Skip the recording rather than the whole channel, the next one may get a good file
+            if let Err(err) = message.write_audio(audio) {
+                error!(
+                    channel = channel.name.as_str(), audio:% = message.uuid;
+                    "Dropping recording: {err:#}"
+                );
+                let uuid = message.uuid;
+                message.discard();
+                let _ = self.web_tx.send(UiMessage::Discarded {
+                    idx: idx as u32,
+                    name: channel.name.to_owned(),
+                    uuid,
+                });
+                continue;
+            }
+
+            message.tone = detected.or(message.tone);
+            if active {
+                message.signal += duration;
+            }
+
+            if message.duration() >= channel.max_duration {
+                state.previous = Some(message.uuid);
+                finalize.push((idx, message));
+            } else {
+                state.recording = Some(message);
+            }
+        }
+
+        // The viewer keeps running the radio after its window is closed
+        #[cfg(feature = "debug")]
+        let _ = self.debug.send(crate::signal::debug::Frame {
+            iq: self.demodulator.iq().to_owned(),
+            center_freq,
+            channels: markers.clone(),
+        });
 
-            let audio = self.demodulator.audio(offset, channel.gain);
-            message.write_audio(audio);
+        if send_spectrum {
+            let bins = self.spectrum.process(self.demodulator.iq());
+            self.spectrum_feed.send(&SpectrumRow {
+                radio: self.radio.name.clone(),
+                center_freq,
+                sample_rate: self.radio.sample_rate,
+                channel_width: 2 * IQ_CUTOFF_FREQ as u32,
+                bins: bins.into_iter().map(|x| x.round() as i8).collect(),
+                channels: markers,
+            });
         }
 
-        for index in finalize {
-            self.finalize_recording(index).unwrap();
+        for (index, message) in finalize {
+            self.finalize_recording(index, message);
         }
+
+        self.update_scanner();
+        Ok(())
     }
 
-    fn finalize_recording(&mut self, index: usize) -> Result<()> {
-        if let Some(Message { uuid, wav, buffer }) = self.recordings[index].take() {
-            self.web_tx
-                .send(UiMessage::Processing { idx: index as u32 })?;
-            wav.finalize().unwrap();
+    /// Switches to a new config without interrupting recordings on channels that still exist.
+    /// Sections that need a restart keep their running values until then.
+    fn apply_config(&mut self, mut config: Config) -> Result<()> {
+        // Everything that can fail is built first, so an error leaves the running config untouched
+        let alerts = Arc::new(Alerts::new(
+            &config.alerts,
+            self.config.misc.data_dir.clone(),
+        )?);
 
-            let start = Instant::now();
-            let text = (!buffer.is_empty()).then(|| self.transcriber.transcribe(&buffer).unwrap());
-            let text_ref = text.as_deref();
+        // Every radio gets the new config, so only the first warns about the shared parts
+        let old = &self.config;
+        if old.radios[0].name == self.radio.name {
+            if config.server != old.server
+                || config.misc != old.misc
+                || config.auth != old.auth
+                || config.sync != old.sync
+                || config.mqtt != old.mqtt
+            {
+                warn!("Changes to the server, misc, auth, sync and mqtt configs require a restart");
+            }
 
-            println!(
-                "{} ({:?})",
-                text_ref.unwrap_or("<No Text>"),
-                start.elapsed()
+            let names = |config: &Config| {
+                let mut names = config
+                    .radios
+                    .iter()
+                    .map(|x| x.name.clone())
+                    .collect::<Vec<_>>();
+                names.sort();
+                names
+            };
+            if names(&config) != names(old) {
+                warn!("Adding, removing or renaming radios requires a restart");
+            }
+        }
+        config.server = old.server.clone();
+        config.misc = old.misc.clone();
+        config.auth = old.auth.clone();
+        config.sync = old.sync.clone();
+        config.mqtt = old.mqtt.clone();
+
+        let position = config.radios.iter().position(|x| x.name == self.radio.name);
+        let owned = position.map_or_else(Vec::new, |x| config.radio_channels(x));
+        let mut radio = position.map_or_else(|| self.radio.clone(), |x| config.radios[x].clone());
+        if radio.device_index != self.radio.device_index
+            || radio.serial != self.radio.serial
+            || radio.sample_rate != self.radio.sample_rate
+        {
+            warn!(
+                radio = radio.name.as_str();
+                "Changes to device_index, serial and sample_rate require a restart"
             );
-            self.database.lock().insert_message(text_ref, uuid)?;
+            radio.device_index = self.radio.device_index;
+            radio.serial = self.radio.serial.clone();
+            radio.sample_rate = self.radio.sample_rate;
+        }
+        let scanner = scanner(&config, &radio, &owned);
+
+        // Channels are matched up by name, so edited channels keep recording
+        for idx in 0..self.channels.len() {
+            let name = &self.config.channels[idx].name;
+            if !owned.iter().any(|&x| &config.channels[x].name == name) {
+                if let Some(message) = self.channels[idx].recording.take() {
+                    self.finalize_recording(idx, message);
+                }
+            }
+        }
+
+        let mut states = mem::take(&mut self.channels)
+            .into_iter()
+            .zip(&self.config.channels)
+            .map(|(state, channel)| (channel.name.clone(), state))
+            .collect::<HashMap<_, _>>();
+        let channels = config
+            .channels
+            .iter()
+            .map(|x| states.remove(&x.name).unwrap_or_else(Channel::new))
+            .collect();
+
+        let gain_changed = radio.tuner_gain != self.radio.tuner_gain || radio.agc != self.radio.agc;
+        let old_center = self.center_freq();
+        logger::init(&config.log);
+        self.config = config;
+        self.radio = radio;
+        self.owned = owned;
+        self.channels = channels;
+        self.alerts = alerts;
+        self.scanner = scanner;
+
+        if gain_changed {
+            let gain = supported_gain(&self.radio, self.device.tuner_gains());
+            self.agc = self
+                .radio
+                .agc
+                .then(|| Agc::new(self.device.tuner_gains().to_vec(), gain));
+            self.device.set_tuner_gain(gain);
+            self.report_gain(gain);
+        }
+
+        // A new scanner starts back at its first group, so it always needs a retune
+        if self.scanner.is_some() || self.center_freq() != old_center {
+            self.retune();
+        }
+
+        Ok(())
+    }
+
+    fn center_freq(&self) -> u32 {
+        match &self.scanner {
+            Some(scanner) => scanner.center(),
+            None => self.radio.center_freq,
+        }
+    }
+
+    /// Indices of the channels that can be received at the current center frequency.
+    fn audible(&self) -> &[usize] {
+        match &self.scanner {
+            Some(scanner) => scanner.channels(),
+            None => &self.owned,
+        }
+    }
+
+    fn update_scanner(&mut self) {
+        let Some(scanner) = &mut self.scanner else {
+            return;
+        };
+
+        let elapsed = (BUFFER_SIZE / 2) as f32 / SAMPLE_RATE as f32;
+        let busy = scanner
+            .channels()
+            .iter()
+            .any(|&x| self.channels[x].open || self.channels[x].recording.is_some());
+        if scanner.update(elapsed, busy).is_some() {
+            self.retune();
+        }
+    }
+
+    /// Moves the tuner to the current center frequency, ending any recordings it can no longer hear.
+    fn retune(&mut self) {
+        let center_freq = self.center_freq();
+        self.device.set_center_freq(center_freq);
+
+        for idx in 0..self.channels.len() {
+            if self.audible().contains(&idx) {
+                // Audio heard on the last visit is stale by now
+                self.channels[idx].pre_roll.clear();
+                self.channels[idx].tone.reset();
+                self.channels[idx].open = false;
+            } else if let Some(message) = self.channels[idx].recording.take() {
+                self.finalize_recording(idx, message);
+            }
+        }
+    }
+
+    fn report_gain(&self, gain: i32) {
+        let radio = self.radio.name.as_str();
+        info!(radio; "Tuner gain set to {:.1} dB", gain as f32 / 10.0);
+        self.radio_metrics.tuner_gain.store(gain, Ordering::Relaxed);
+        let _ = self.web_tx.send(UiMessage::TunerGain {
+            radio: radio.to_owned(),
+            gain: gain as f32 / 10.0,
+        });
+    }
+
+    /// Saves a finished recording and queues it to be transcribed, logging rather than returning
+    /// errors as they only affect it.
+    fn finalize_recording(&mut self, index: usize, message: Message) {
+        let uuid = message.uuid;
+        if let Err(err) = self.save_recording(index, message) {
+            let channel = self.config.channels[index].name.as_str();
+            error!(channel, audio:% = uuid; "Failed to save recording: {err:#}");
+        }
+    }
+
+    fn save_recording(&mut self, index: usize, message: Message) -> Result<()> {
+        let channel = &self.config.channels[index];
+        let Message {
+            uuid,
+            previous,
+            path,
+            wav,
+            buffer,
+            signal,
+            tone,
+        } = message;
+        wav.finalize()?;
 
-            self.web_tx.send(UiMessage::Complete(database::Message {
-                date: date_time(),
-                audio: uuid,
-                text,
-            }))?;
+        if previous.is_none() && signal < channel.min_duration {
+            debug!(channel = channel.name.as_str(); "Discarded {signal:.2}s burst");
+            fs::remove_file(path)?;
+            self.metrics
+                .discarded_messages
+                .fetch_add(1, Ordering::Relaxed);
+            self.web_tx.send(UiMessage::Discarded {
+                idx: index as u32,
+                name: channel.name.to_owned(),
+                uuid,
+            })?;
+            return Ok(());
         }
 
+        self.web_tx.send(UiMessage::Processing {
+            idx: index as u32,
+            name: channel.name.to_owned(),
+            uuid,
+        })?;
+
+        self.transcriptions.push(Job {
+            idx: index as u32,
+            channel: channel.name.to_owned(),
+            uuid,
+            previous,
+            tone: tone.map(|x| x.to_string()),
+            buffer,
+            alerts: self.alerts.clone(),
+        });
         Ok(())
     }
 }
 
+/// The supported gain closest to the configured one, warning if they're different.
+fn supported_gain(radio: &RadioConfig, gains: &[i32]) -> i32 {
+    let wanted = radio.tuner_gain;
+    let Some(&gain) = gains.iter().min_by_key(|&&x| (x - wanted).abs()) else {
+        return wanted;
+    };
+
+    if gain != wanted {
+        let supported = gains
+            .iter()
+            .map(|x| format!("{:.1}", *x as f32 / 10.0))
+            .collect::<Vec<_>>();
+        warn!(
+            radio = radio.name.as_str();
+            "tuner_gain {:.1} dB isn't supported, using {:.1} dB. The tuner supports {} dB",
+            wanted as f32 / 10.0, gain as f32 / 10.0, supported.join(", ")
+        );
+    }
+    gain
+}
+
+/// Only scans if the radio has channels, which it won't if it was removed from the config.
+fn scanner(config: &Config, radio: &RadioConfig, owned: &[usize]) -> Option<Scanner> {
+    let scan = radio.scan.as_ref().filter(|_| !owned.is_empty())?;
+    Some(Scanner::new(
+        &config.channels,
+        owned.to_vec(),
+        radio.sample_rate,
+        scan,
+    ))
+}
+
+impl Channel {
+    fn new() -> Self {
+        Self {
+            recording: None,
+            demodulate: demodulate::ChannelState::default(),
+            since_active: f32::INFINITY,
+            open: false,
+            pre_roll: VecDeque::new(),
+            previous: None,
+            tone: ToneDetector::new(),
+            noise_floor: f32::INFINITY,
+        }
+    }
+
+    fn update_noise_floor(&mut self, rms: f32) {
+        if rms < self.noise_floor {
+            self.noise_floor = rms;
+        } else {
+            self.noise_floor += (rms - self.noise_floor) * NOISE_FLOOR_RISE;
+        }
+    }
+
+    fn push_pre_roll(&mut self, audio: &[f32], seconds: f32) {
+        let capacity = (seconds * WAVE_SAMPLE_RATE as f32) as usize;
+        self.pre_roll.extend(audio);
+
+        let overflow = self.pre_roll.len().saturating_sub(capacity);
+        self.pre_roll.drain(..overflow);
+    }
+}
+
 impl Message {
-    fn new(data_dir: &Path) -> Self {
+    /// Creates the recording's file and writes the audio from before the squelch opened.
+    fn start(data_dir: &Path, previous: Option<Uuid>, pre_roll: Vec<f32>) -> Result<Self> {
         let uuid = Uuid::new_v4();
         let path = data_dir.join("audio").join(format!("{}.wav", uuid));
-        let wav = WavWriter::create(path, WAVE_SPEC).unwrap();
+        let wav = WavWriter::create(&path, WAVE_SPEC)
+            .with_context(|| format!("creating `{}`", path.display()))?;
 
-        Message {
+        let mut message = Message {
             uuid,
+            previous,
+            path,
             wav,
             buffer: Vec::new(),
+            signal: 0.0,
+            tone: None,
+        };
+        if let Err(err) = message.write_audio(pre_roll) {
+            message.discard();
+            return Err(err);
         }
+
+        Ok(message)
     }
 
-    fn write_audio(&mut self, audio: Vec<f32>) {
+    /// Closes and deletes the recording's file.
+    fn discard(self) {
+        drop(self.wav);
+        let _ = fs::remove_file(self.path);
+    }
+
+    fn duration(&self) -> f32 {
+        self.wav.len() as f32 / WAVE_SAMPLE_RATE as f32
+    }
+
+    fn write_audio(&mut self, audio: Vec<f32>) -> Result<()> {
         for sample in &audio {
-            self.wav
-                .write_sample((sample * i8::MAX as f32) as i8)
-                .unwrap()
+            self.wav.write_sample((sample * i8::MAX as f32) as i8)?;
         }
 
         self.buffer.extend(
@@ -170,5 +792,6 @@ impl Message {
                 .into_iter()
                 .down_sample(WAVE_SAMPLE_RATE, TRANSCRIBE_SAMPLE_RATE),
         );
+        Ok(())
     }
 }
@@ -0,0 +1,142 @@
+use std::{
+    env, fs,
+    fs::File,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use uuid::Uuid;
+use zip::{result::ZipError, write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::web::database::{Database, Message};
+
+const DATABASE: &str = "data.db";
+
+pub struct Backup {
+    pub messages: usize,
+    /// Messages whose audio had already been deleted.
+    pub missing_audio: usize,
+}
+
+pub struct Restore {
+    pub added: usize,
+    /// Messages that were already in the archive, from an earlier restore or the same station.
+    pub skipped: usize,
+    pub audio: usize,
+}
+
+/// A scratch directory that's removed when dropped.
+struct TempDir(PathBuf);
+
+/// Backs up `data_dir` to a ZIP of the database and the audio it refers to, without stopping recording.
+pub fn backup(data_dir: &Path, path: &Path) -> Result<Backup> {
+    let temp = TempDir::new()?;
+    let database = temp.0.join(DATABASE);
+    Database::new(data_dir)?.lock().backup(&database)?;
+    let messages = Database::new(&temp.0)?.lock().get_messages()?;
+
+    let mut zip = ZipWriter::new(
+        File::create(path).with_context(|| format!("creating `{}`", path.display()))?,
+    );
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .large_file(true);
+
+    zip.start_file(DATABASE, options)?;
+    io::copy(&mut File::open(&database)?, &mut zip)?;
+
+    let mut missing_audio = 0;
+    for message in &messages {
+        let name = audio_name(message.audio);
+        let mut audio = match File::open(data_dir.join(&name)) {
+            Ok(audio) => audio,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                missing_audio += 1;
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        zip.start_file(&name, options)?;
+        io::copy(&mut audio, &mut zip)?;
+    }
+
+    zip.finish()?;
+    Ok(Backup {
+        messages: messages.len(),
+        missing_audio,
+    })
+}
+
+/// Merges the archive at `path` into `data_dir`, labeling the archive's own messages with
+/// `station` so they can be told apart from other stations'.
+/// Messages are matched by their audio's UUID, so restoring the same archive twice is harmless.
+pub fn restore(data_dir: &Path, path: &Path, station: Option<&str>) -> Result<Restore> {
+    let mut zip = ZipArchive::new(
+        File::open(path).with_context(|| format!("opening `{}`", path.display()))?,
+    )?;
+
+    // Opening it as a `Database` also brings it up to the current schema
+    let temp = TempDir::new()?;
+    io::copy(
+        &mut zip.by_name(DATABASE).context("archive has no database")?,
+        &mut File::create(temp.0.join(DATABASE))?,
+    )?;
+    let messages = Database::new(&temp.0)?.lock().get_messages()?;
+
+    let database = Database::new(data_dir)?;
+    let mut restore = Restore {
+        added: 0,
+        skipped: 0,
+        audio: 0,
+    };
+
+    // Oldest first, so they're added in the order they were heard
+    for message in messages.iter().rev() {
+        // The audio goes in first, so a message is never visible without it
+        let name = audio_name(message.audio);
+        let audio_path = data_dir.join(&name);
+        if !audio_path.exists() {
+            match zip.by_name(&name) {
+                Ok(mut audio) => {
+                    let partial = audio_path.with_extension("wav.part");
+                    io::copy(&mut audio, &mut File::create(&partial)?)?;
+                    fs::rename(partial, audio_path)?;
+                    restore.audio += 1;
+                }
+                Err(ZipError::FileNotFound) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        let message = Message {
+            station: message.station.clone().or(station.map(str::to_owned)),
+            ..message.clone()
+        };
+        match database.lock().import_message(&message)? {
+            true => restore.added += 1,
+            false => restore.skipped += 1,
+        }
+    }
+
+    Ok(restore)
+}
+
+fn audio_name(uuid: Uuid) -> String {
+    format!("audio/{uuid}.wav")
+}
+
+impl TempDir {
+    fn new() -> Result<Self> {
+        let path = env::temp_dir().join(format!("radio-history-{}", Uuid::new_v4()));
+        fs::create_dir_all(&path)?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
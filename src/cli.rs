@@ -0,0 +1,144 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    archive,
+    config::Config,
+    device,
+    export::{self, Format},
+    web::{
+        auth,
+        database::{Database, Filter},
+    },
+    CONFIG_PATH,
+};
+
+const USAGE: &str = "Usage: radio-history [command]
+
+Starts recording if no command is given.
+
+Commands:
+  hash-password  Reads a password from stdin and prints its hash, for `auth.users` in the config
+  new-token      Generates an API token and prints it with its hash, for `auth.tokens` in the config
+  devices        Lists the connected RTL-SDRs with their serial numbers, for `radios.serial` in the config
+  export <csv|jsonl|zip> [--from DATE] [--to DATE] [--channel NAME] [--output FILE]
+                 Exports messages, to stdout unless an output file is given. Dates are UTC,
+                 as YYYY-MM-DD [HH:MM[:SS]], and a bare date for --to includes that day
+  backup <FILE>  Backs up the database and audio to a file, safe to run while recording
+  restore <FILE> [--station NAME]
+                 Merges a backup into this station's archive, skipping messages it already has.
+                 The backup's own messages are labeled with the station, if one is given";
+
+pub fn run(args: &[String]) -> Result<()> {
+    let command = args[0].as_str();
+    match command {
+        "hash-password" => {
+            eprint!("Password: ");
+            io::stderr().flush()?;
+            let mut password = String::new();
+            io::stdin().read_line(&mut password)?;
+
+            let password = password.trim_end_matches(['\r', '\n']);
+            if password.is_empty() {
+                bail!("Password must not be empty");
+            }
+            println!("{}", auth::hash_password(password)?);
+        }
+        "new-token" => {
+            let token = auth::new_token();
+            println!("Token: {token}");
+            println!("Hash:  {}", auth::hash_token(&token));
+        }
+        "devices" => {
+            let devices = device::list();
+            if devices.is_empty() {
+                println!("No RTL-SDRs found");
+            }
+            for device in devices {
+                println!(
+                    "#{} {} (serial `{}`)",
+                    device.index, device.name, device.serial
+                );
+            }
+        }
+        "export" => export(&args[1..])?,
+        "backup" => {
+            let data_dir = Config::load(CONFIG_PATH)?.misc.data_dir;
+            let backup = archive::backup(&data_dir, path_arg(args)?)?;
+            println!("Backed up {} messages", backup.messages);
+            if backup.missing_audio > 0 {
+                println!("{} of them had no audio", backup.missing_audio);
+            }
+        }
+        "restore" => {
+            let (path, station) = match &args[1..] {
+                [path] => (path, None),
+                [path, option, station] if option == "--station" => (path, Some(station.as_str())),
+                _ => bail!("Expected a file and optionally `--station NAME`\n\n{USAGE}"),
+            };
+            let data_dir = Config::load(CONFIG_PATH)?.misc.data_dir;
+            let restore = archive::restore(&data_dir, Path::new(path), station)?;
+            println!(
+                "Added {} messages and {} audio files, skipped {} already here",
+                restore.added, restore.audio, restore.skipped
+            );
+        }
+        "help" | "--help" | "-h" => println!("{USAGE}"),
+        _ => bail!("Unknown command `{command}`\n\n{USAGE}"),
+    }
+
+    Ok(())
+}
+
+fn path_arg(args: &[String]) -> Result<&Path> {
+    match args {
+        [_, path] => Ok(Path::new(path)),
+        _ => bail!("Expected one file\n\n{USAGE}"),
+    }
+}
+
+fn export(args: &[String]) -> Result<()> {
+    let Some(format) = args.first() else {
+        bail!("Missing export format\n\n{USAGE}");
+    };
+    let format = format.parse::<Format>()?;
+
+    let [mut from, mut to, mut channel, mut output] = [None; 4];
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        let value = match option.as_str() {
+            "--from" => &mut from,
+            "--to" => &mut to,
+            "--channel" => &mut channel,
+            "--output" => &mut output,
+            _ => bail!("Unknown option `{option}`\n\n{USAGE}"),
+        };
+        *value = Some(
+            options
+                .next()
+                .with_context(|| format!("Missing value for `{option}`"))?
+                .as_str(),
+        );
+    }
+
+    let filter = Filter::parse(from, to, channel)?;
+    let config = Config::load(CONFIG_PATH)?;
+    let data_dir = &config.misc.data_dir;
+    let messages = Database::new(data_dir)?.lock().export_messages(&filter)?;
+
+    match output {
+        Some(path) => {
+            let file = File::create(path).with_context(|| format!("creating `{path}`"))?;
+            export::write(format, &messages, &filter, data_dir, BufWriter::new(file))?;
+            eprintln!("Exported {} messages to `{path}`", messages.len());
+        }
+        None => export::write(format, &messages, &filter, data_dir, io::stdout().lock())?,
+    }
+
+    Ok(())
+}
@@ -1,48 +1,842 @@
-use std::{fs, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+};
 
-use anyhow::Result;
-use serde::Deserialize;
+use anyhow::{bail, Result};
+use pbkdf2::password_hash::PasswordHash;
+use serde::{
+    de::{
+        value::{MapAccessDeserializer, SeqAccessDeserializer},
+        MapAccess, SeqAccess, Visitor,
+    },
+    Deserialize, Deserializer, Serialize,
+};
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, Item, Table};
 
-#[derive(Debug, Deserialize)]
+use crate::{
+    alerts,
+    consts::{IQ_CUTOFF_FREQ, SAMPLE_RATE},
+    logger,
+    misc::serialize_f32,
+    signal::{demodulate::Mode, tone::Tone},
+    web::{auth::Role, tls},
+};
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub server: ServerConfig,
-    pub radio: RadioConfig,
+    /// One or more `[[radios]]`. A single `[radio]` table also works.
+    #[serde(alias = "radio", deserialize_with = "one_or_many")]
+    pub radios: Vec<RadioConfig>,
     pub misc: MiscConfig,
+    #[serde(default)]
+    pub log: LogConfig,
+    /// Where the first radio's `scan` was before there could be several radios.
+    #[serde(default)]
+    scan: Option<ScanConfig>,
+    /// Require a login or API token to use the web UI and API.
+    pub auth: Option<AuthConfig>,
+    /// Push completed messages to a central radio-history server.
+    pub sync: Option<SyncConfig>,
+    /// Publish events to an MQTT broker.
+    pub mqtt: Option<MqttConfig>,
+    #[serde(default)]
+    pub alerts: Vec<AlertConfig>,
     pub channels: Vec<ChannelConfig>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
     pub workers: usize,
+    /// Serve HTTPS (and secure websockets) instead of plain HTTP.
+    pub tls: Option<TlsConfig>,
+    /// Accept messages pushed by other stations, from tokens with the ingest role.
+    #[serde(default)]
+    pub ingest: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct LogConfig {
+    #[serde(default)]
+    pub level: logger::Level,
+    /// Levels for specific modules, like `"radio_history::web" = "debug"` or `afire = "warn"`.
+    #[serde(default)]
+    pub targets: BTreeMap<String, logger::Level>,
+    #[serde(default)]
+    pub format: logger::Format,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TlsConfig {
+    /// PEM certificate chain, leaf first.
+    pub cert: PathBuf,
+    /// PEM private key.
+    pub key: PathBuf,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct RadioConfig {
-    pub device_index: i32,
+    /// Channels pick their radio by this name.
+    #[serde(default = "default_radio_name")]
+    pub name: String,
+    /// Which device to open, defaulting to the first. Indices can change as devices are plugged in.
+    pub device_index: Option<i32>,
+    /// Opens the device with this serial number instead, as listed by `radio-history devices`.
+    pub serial: Option<String>,
     pub center_freq: u32,
     pub sample_rate: u32,
+    /// Initial tuner gain in tenths of a dB.
     pub tuner_gain: i32,
+    /// Step the tuner gain automatically to avoid clipping and weak signals.
+    #[serde(default)]
+    pub agc: bool,
+    /// Hop between groups of this radio's channels instead of staying at `center_freq`.
+    pub scan: Option<ScanConfig>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct MiscConfig {
     pub transcribe_model: String,
     pub data_dir: PathBuf,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ScanConfig {
+    /// Seconds to listen to each group of channels before moving on.
+    #[serde(default = "default_dwell")]
+    pub dwell: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AuthConfig {
+    /// Days until a login expires.
+    #[serde(default = "default_session_days")]
+    pub session_days: f32,
+    #[serde(default)]
+    pub users: Vec<UserConfig>,
+    #[serde(default)]
+    pub tokens: Vec<TokenConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SyncConfig {
+    /// Name this station's messages are labeled with on the central server.
+    pub station: String,
+    /// Base URL of the central server, like `https://central.example.com:8081`.
+    pub upstream: String,
+    /// API token for the central server, with the ingest role.
+    pub token: String,
+    /// Seconds to wait before retrying when the central server can't be reached.
+    #[serde(default = "default_retry")]
+    pub retry: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MqttConfig {
+    /// Hostname of the broker.
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    #[serde(default = "default_mqtt_client_id")]
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Prepended to every topic, like `{prefix}/messages`.
+    #[serde(default = "default_mqtt_client_id")]
+    pub prefix: String,
+}
+
+/// Fires when a transcript matches any of the keywords or regexes.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AlertConfig {
+    pub name: String,
+    /// Words or phrases, ignoring case and whether words are split by spaces, hyphens or punctuation.
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    #[serde(default)]
+    pub regexes: Vec<String>,
+    /// Names of the channels to watch, or all of them if empty.
+    #[serde(default)]
+    pub channels: Vec<String>,
+    /// URL to POST the alert to as JSON.
+    pub webhook: Option<String>,
+    /// Program and arguments to run, with the alert in `ALERT_*` environment variables.
+    #[serde(default)]
+    pub command: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct UserConfig {
+    pub name: String,
+    /// Password hash, from `radio-history hash-password`.
+    pub password: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TokenConfig {
+    /// What the token is used for.
+    pub name: String,
+    /// SHA-256 hash of the token, from `radio-history new-token`.
+    pub hash: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ChannelConfig {
     pub name: String,
     pub freq: u32,
+    #[serde(serialize_with = "serialize_f32")]
     pub squelch: f32,
+    #[serde(serialize_with = "serialize_f32")]
     pub gain: f32,
+    #[serde(default)]
+    pub mode: Mode,
+    /// Name of the radio that receives it, defaulting to the first one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub radio: Option<String>,
+    /// Seconds to keep recording after the signal drops below the squelch.
+    #[serde(default = "default_hang_time", serialize_with = "serialize_f32")]
+    pub hang_time: f32,
+    /// Seconds of audio from before the squelch opened to include in recordings.
+    #[serde(default = "default_pre_roll", serialize_with = "serialize_f32")]
+    pub pre_roll: f32,
+    /// Recordings with less than this many seconds of signal are discarded.
+    #[serde(default = "default_min_duration", serialize_with = "serialize_f32")]
+    pub min_duration: f32,
+    /// Recordings longer than this many seconds are split into consecutive messages.
+    #[serde(default = "default_max_duration", serialize_with = "serialize_f32")]
+    pub max_duration: f32,
+    /// Only record while this CTCSS tone or DCS code is present.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tone: Option<Tone>,
+    /// When scanning, revisit this channel between every other group.
+    #[serde(default)]
+    pub priority: bool,
 }
 
 impl Config {
-    pub fn load(path: &str) -> Result<Config> {
-        let config = fs::read_to_string(path)?;
-        Ok(toml::from_str(&config)?)
+    pub fn load(path: impl AsRef<Path>) -> Result<Config> {
+        let path = path.as_ref();
+        let config = Self::parse(&fs::read_to_string(path)?)?;
+        config.check(path)?;
+        Ok(config)
+    }
+
+    /// Like [`Config::load`], but also makes sure `misc.data_dir` can be written to. That means
+    /// writing to it, so it's left out of validation, which runs on every reload.
+    pub fn load_startup(path: impl AsRef<Path>) -> Result<Config> {
+        let path = path.as_ref();
+        let config = Self::parse(&fs::read_to_string(path)?)?;
+
+        let mut problems = config.validate();
+        let data_dir = &config.misc.data_dir;
+        let probe = data_dir.join(".write-test");
+        let writable = fs::create_dir_all(data_dir)
+            .and_then(|_| fs::write(&probe, []))
+            .and_then(|_| fs::remove_file(&probe));
+        if let Err(err) = writable {
+            let data_dir = data_dir.display();
+            problems.push(format!(
+                "misc.data_dir: `{data_dir}` is not writable: {err}"
+            ));
+        }
+
+        report(path, &problems)?;
+        Ok(config)
+    }
+
+    fn parse(config: &str) -> Result<Config> {
+        let mut config = toml::from_str::<Config>(config)?;
+        if let (Some(scan), Some(radio)) = (config.scan.take(), config.radios.first_mut()) {
+            radio.scan.get_or_insert(scan);
+        }
+        Ok(config)
+    }
+
+    /// Index of the radio that receives a channel, if it names one that exists.
+    pub fn channel_radio(&self, channel: &ChannelConfig) -> Option<usize> {
+        match &channel.radio {
+            Some(name) => self.radios.iter().position(|x| &x.name == name),
+            None => (!self.radios.is_empty()).then_some(0),
+        }
+    }
+
+    /// Indices of the channels received by a radio.
+    pub fn radio_channels(&self, radio: usize) -> Vec<usize> {
+        (0..self.channels.len())
+            .filter(|&x| self.channel_radio(&self.channels[x]) == Some(radio))
+            .collect()
+    }
+
+    /// Like [`Config::validate`], but combines any problems into one error.
+    pub fn check(&self, path: &Path) -> Result<()> {
+        report(path, &self.validate())
+    }
+
+    /// Writes the channels back to the config file, leaving the rest of it (and its comments) untouched.
+    /// Existing channel tables are edited in place, so comments inside them are kept too.
+    pub fn save_channels(&self, path: &Path) -> Result<()> {
+        let mut document = fs::read_to_string(path)?.parse::<DocumentMut>()?;
+        let mut existing = document
+            .get("channels")
+            .and_then(Item::as_array_of_tables)
+            .map(|x| x.iter().cloned().map(Some).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        let name = |table: &Table| table.get("name")?.as_str().map(str::to_owned);
+        let claimed = |table: &Table| {
+            let name = name(table);
+            self.channels.iter().any(|x| name.as_ref() == Some(&x.name))
+        };
+
+        let mut tables = ArrayOfTables::new();
+        for (idx, channel) in self.channels.iter().enumerate() {
+            // Tables are matched up by name, or by position if the channel was renamed
+            let by_name = existing.iter().position(|x| {
+                x.as_ref()
+                    .is_some_and(|x| name(x).as_ref() == Some(&channel.name))
+            });
+            let by_position = existing
+                .get(idx)
+                .and_then(Option::as_ref)
+                .is_some_and(|x| !claimed(x))
+                .then_some(idx);
+            let mut table = by_name
+                .or(by_position)
+                .and_then(|x| existing[x].take())
+                .unwrap_or_default();
+
+            let new = toml::to_string(channel)?.parse::<DocumentMut>()?;
+            update_table(&mut table, new.as_table().clone());
+            tables.push(table);
+        }
+
+        document["channels"] = match tables.is_empty() {
+            true => value(Array::new()),
+            false => Item::ArrayOfTables(tables),
+        };
+        fs::write(path, document.to_string())?;
+        Ok(())
+    }
+
+    /// Checks for values that would crash or silently misbehave at runtime, returning every problem found.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut problem = |path: &str, message: String| problems.push(format!("{path}: {message}"));
+
+        if self.server.workers == 0 {
+            problem("server.workers", "must be at least 1".into());
+        }
+        if let Some(tls) = &self.server.tls {
+            if let Err(err) = tls::load(tls) {
+                problem("server.tls", format!("{err:#}"));
+            }
+        }
+
+        if self.server.ingest && self.auth.is_none() {
+            problem(
+                "server.ingest",
+                "needs [auth], or anyone could push messages".into(),
+            );
+        }
+
+        if self.radios.is_empty() {
+            problem("radios", "needs at least one radio".into());
+        }
+
+        let mut radio_names = HashSet::new();
+        let mut devices = HashSet::new();
+        for (idx, radio) in self.radios.iter().enumerate() {
+            let path = |field: &str| format!("radios[{idx}].{field}");
+
+            if radio.name.trim().is_empty() {
+                problem(&path("name"), "must not be empty".into());
+            } else if !radio_names.insert(&radio.name) {
+                problem(
+                    &path("name"),
+                    format!("`{}` is used by another radio", radio.name),
+                );
+            }
+
+            let device = match (radio.device_index, &radio.serial) {
+                (Some(_), Some(_)) => {
+                    problem(&path("serial"), "can't be used with device_index".into());
+                    None
+                }
+                (_, Some(serial)) => Some(format!("serial `{serial}`")),
+                (index, None) => Some(format!("device_index {}", index.unwrap_or(0))),
+            };
+            if let Some(device) = device.filter(|x| !devices.insert(x.clone())) {
+                problem(
+                    &path("device_index"),
+                    format!("{device} is used by another radio"),
+                );
+            }
+
+            if radio.sample_rate != SAMPLE_RATE {
+                problem(
+                    &path("sample_rate"),
+                    format!("must be {SAMPLE_RATE}, the rate the demodulator is built for"),
+                );
+            }
+
+            if let Some(scan) = &radio.scan {
+                if !(scan.dwell.is_finite() && scan.dwell > 0.0) {
+                    problem(
+                        &path("scan.dwell"),
+                        format!("{} must be positive", scan.dwell),
+                    );
+                }
+                if self.radio_channels(idx).is_empty() {
+                    problem(
+                        &path("scan"),
+                        "scanning requires at least one channel".into(),
+                    );
+                }
+            }
+        }
+
+        if let Some(auth) = &self.auth {
+            if !(auth.session_days.is_finite() && auth.session_days > 0.0) {
+                problem(
+                    "auth.session_days",
+                    format!("{} must be positive", auth.session_days),
+                );
+            }
+            if auth.users.is_empty() && auth.tokens.is_empty() {
+                problem("auth", "needs at least one user or token".into());
+            }
+
+            let mut names = HashSet::new();
+            for (idx, user) in auth.users.iter().enumerate() {
+                if !names.insert(&user.name) {
+                    problem(
+                        &format!("auth.users[{idx}].name"),
+                        format!("`{}` is used by another user", user.name),
+                    );
+                }
+                if PasswordHash::new(&user.password).is_err() {
+                    problem(
+                        &format!("auth.users[{idx}].password"),
+                        "must be a hash from `radio-history hash-password`".into(),
+                    );
+                }
+            }
+
+            for (idx, token) in auth.tokens.iter().enumerate() {
+                if token.hash.len() != 64 || !token.hash.chars().all(|x| x.is_ascii_hexdigit()) {
+                    problem(
+                        &format!("auth.tokens[{idx}].hash"),
+                        "must be a hash from `radio-history new-token`".into(),
+                    );
+                }
+            }
+        }
+
+        if let Some(sync) = &self.sync {
+            if sync.station.trim().is_empty() {
+                problem("sync.station", "must not be empty".into());
+            }
+            if !(sync.upstream.starts_with("http://") || sync.upstream.starts_with("https://")) {
+                problem(
+                    "sync.upstream",
+                    format!("`{}` must be an http:// or https:// URL", sync.upstream),
+                );
+            }
+            if !(sync.retry.is_finite() && sync.retry > 0.0) {
+                problem("sync.retry", format!("{} must be positive", sync.retry));
+            }
+        }
+
+        if let Some(mqtt) = &self.mqtt {
+            if mqtt.client_id.is_empty() {
+                problem("mqtt.client_id", "must not be empty".into());
+            }
+            if mqtt.prefix.is_empty() || mqtt.prefix.contains(['+', '#']) {
+                problem(
+                    "mqtt.prefix",
+                    format!(
+                        "`{}` must be a non-empty topic without wildcards",
+                        mqtt.prefix
+                    ),
+                );
+            }
+            if mqtt.password.is_some() && mqtt.username.is_none() {
+                problem("mqtt.password", "needs a username too".into());
+            }
+        }
+
+        for (idx, alert) in self.alerts.iter().enumerate() {
+            let path = |field: &str| format!("alerts[{idx}].{field}");
+            if alert.keywords.is_empty() && alert.regexes.is_empty() {
+                problem(
+                    &path("keywords"),
+                    "needs at least one keyword or regex".into(),
+                );
+            }
+            if let Err(err) = alerts::patterns(alert) {
+                problem(&path("regexes"), err.to_string());
+            }
+            for channel in &alert.channels {
+                if !self.channels.iter().any(|x| &x.name == channel) {
+                    problem(
+                        &path("channels"),
+                        format!("no channel is named `{channel}`"),
+                    );
+                }
+            }
+            if let Some(webhook) = &alert.webhook {
+                if !(webhook.starts_with("http://") || webhook.starts_with("https://")) {
+                    problem(
+                        &path("webhook"),
+                        format!("`{webhook}` must be an http:// or https:// URL"),
+                    );
+                }
+            }
+        }
+
+        if !fs::exists(&self.misc.transcribe_model).unwrap_or(false) {
+            problem(
+                "misc.transcribe_model",
+                format!("model file `{}` not found", self.misc.transcribe_model),
+            );
+        }
+
+        let mut names = HashSet::new();
+        for (idx, channel) in self.channels.iter().enumerate() {
+            let path = |field: &str| format!("channels[{idx}].{field}");
+
+            if channel.name.trim().is_empty() {
+                problem(&path("name"), "must not be empty".into());
+            } else if !names.insert(&channel.name) {
+                problem(
+                    &path("name"),
+                    format!("`{}` is used by another channel", channel.name),
+                );
+            }
+
+            match self.channel_radio(channel).map(|x| &self.radios[x]) {
+                Some(radio) => {
+                    let bandwidth = (radio.sample_rate / 2).saturating_sub(IQ_CUTOFF_FREQ as u32);
+                    let offset = channel.freq.abs_diff(radio.center_freq);
+                    if radio.scan.is_none() && offset > bandwidth {
+                        problem(
+                            &path("freq"),
+                            format!(
+                                "{} Hz is {offset} Hz from the center_freq of radio `{}`, but only ±{bandwidth} Hz of the sampled bandwidth is usable",
+                                channel.freq, radio.name
+                            ),
+                        );
+                    }
+                }
+                None => {
+                    if let Some(name) = &channel.radio {
+                        problem(&path("radio"), format!("no radio is named `{name}`"));
+                    }
+                }
+            }
+
+            // The RMS of normalized IQ samples can't exceed √2
+            if !(channel.squelch > 0.0 && channel.squelch < 2f32.sqrt()) {
+                problem(
+                    &path("squelch"),
+                    format!("{} must be between 0 and 1.41", channel.squelch),
+                );
+            }
+            if !(channel.gain.is_finite() && channel.gain > 0.0) {
+                problem(&path("gain"), format!("{} must be positive", channel.gain));
+            }
+
+            for (field, value) in [
+                ("hang_time", channel.hang_time),
+                ("pre_roll", channel.pre_roll),
+                ("min_duration", channel.min_duration),
+            ] {
+                if !(value.is_finite() && value >= 0.0) {
+                    problem(&path(field), format!("{value} must not be negative"));
+                }
+            }
+            if !(channel.max_duration > channel.min_duration && channel.max_duration.is_finite()) {
+                problem(
+                    &path("max_duration"),
+                    format!("{} must be greater than min_duration", channel.max_duration),
+                );
+            }
+
+            if let Some(tone) = channel.tone.filter(|x| !x.is_standard()) {
+                problem(
+                    &path("tone"),
+                    format!("{tone} is not a standard tone or code"),
+                );
+            }
+        }
+
+        problems
+    }
+}
+
+/// Sets a table's keys to those in `new`, keeping the formatting and comments of values that didn't change.
+fn update_table(table: &mut Table, new: Table) {
+    table.retain(|key, _| new.contains_key(key));
+    for (key, item) in new {
+        // Like `tone = { ctcss = 100.0 }`, rather than a `[channels.tone]` table
+        let item = match item {
+            Item::Table(x) => value(x.into_inline_table()),
+            x => x,
+        };
+        match table.get_mut(&key) {
+            Some(old) if same_value(old, &item) => {}
+            Some(old) => {
+                // Keep comments after the old value, like `mode = "fm" # or "am"`
+                let decor = old.as_value().map(|x| x.decor().clone());
+                *old = item;
+                if let (Some(decor), Some(value)) = (decor, old.as_value_mut()) {
+                    *value.decor_mut() = decor;
+                }
+            }
+            None => {
+                table.insert(&key, item);
+            }
+        }
+    }
+}
+
+/// Compares values rather than how they're written, so `156_450_000` is left alone.
+fn same_value(a: &Item, b: &Item) -> bool {
+    let parse =
+        |x: &Item| toml::from_str::<toml::Table>(&format!("x = {}", x.to_string().trim())).ok();
+    parse(a).is_some_and(|a| Some(a) == parse(b))
+}
+
+/// Accepts either a single table or an array of them.
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<RadioConfig>, D::Error> {
+    struct OneOrMany;
+
+    impl<'de> Visitor<'de> for OneOrMany {
+        type Value = Vec<RadioConfig>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a table or array of tables")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+            RadioConfig::deserialize(MapAccessDeserializer::new(map)).map(|x| vec![x])
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+            Vec::deserialize(SeqAccessDeserializer::new(seq))
+        }
+    }
+
+    deserializer.deserialize_any(OneOrMany)
+}
+
+/// Combines a config's problems into one error, if it has any.
+fn report(path: &Path, problems: &[String]) -> Result<()> {
+    if !problems.is_empty() {
+        bail!(
+            "Invalid config `{}`:\n  - {}",
+            path.display(),
+            problems.join("\n  - ")
+        );
+    }
+
+    Ok(())
+}
+
+fn default_radio_name() -> String {
+    "radio".into()
+}
+
+fn default_session_days() -> f32 {
+    7.0
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_client_id() -> String {
+    "radio-history".into()
+}
+
+fn default_retry() -> f32 {
+    30.0
+}
+
+fn default_dwell() -> f32 {
+    0.1
+}
+
+fn default_hang_time() -> f32 {
+    1.0
+}
+
+fn default_pre_roll() -> f32 {
+    0.5
+}
+
+fn default_min_duration() -> f32 {
+    0.5
+}
+
+fn default_max_duration() -> f32 {
+    300.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use uuid::Uuid;
+
+    use super::{ChannelConfig, Config};
+
+    const BASE: &str = r#"
+        [server]
+        host = "0.0.0.0"
+        port = 8081
+        workers = 1
+
+        [misc]
+        transcribe_model = "tiny_en.bin"
+        data_dir = "data"
+
+        [[channels]]
+        name = "Marine 16"
+        freq = 156_800_000
+        squelch = 0.01
+        gain = 10.0
+
+        [[channels]]
+        name = "Guard"
+        freq = 121_500_000
+        squelch = 0.01
+        gain = 10.0
+        radio = "airband"
+    "#;
+
+    #[test]
+    fn single_radio_table_still_works() {
+        let config = Config::parse(&format!(
+            "{BASE}
+            [radio]
+            device_index = 1
+            center_freq = 156_800_000
+            sample_rate = 250_000
+            tuner_gain = 10
+
+            [scan]
+            dwell = 0.5"
+        ))
+        .unwrap();
+
+        let [radio] = &config.radios[..] else {
+            panic!("expected one radio");
+        };
+        assert_eq!(radio.name, "radio");
+        assert_eq!(radio.device_index, Some(1));
+        assert_eq!(radio.scan.as_ref().map(|x| x.dwell), Some(0.5));
+        assert_eq!(config.radio_channels(0), [0]);
+        assert_eq!(config.channel_radio(&config.channels[1]), None);
+    }
+
+    #[test]
+    fn channels_pick_their_radio() {
+        let config = Config::parse(&format!(
+            r#"{BASE}
+            [[radios]]
+            name = "marine"
+            center_freq = 156_800_000
+            sample_rate = 250_000
+            tuner_gain = 10
+
+            [[radios]]
+            name = "airband"
+            serial = "00000002"
+            center_freq = 121_500_000
+            sample_rate = 250_000
+            tuner_gain = 300"#
+        ))
+        .unwrap();
+
+        assert_eq!(config.radios[1].serial.as_deref(), Some("00000002"));
+        assert_eq!(config.radio_channels(0), [0]);
+        assert_eq!(config.radio_channels(1), [1]);
+    }
+
+    #[test]
+    fn ingest_needs_auth() {
+        let problem = "server.ingest: needs [auth], or anyone could push messages".to_owned();
+        let mut config = Config::parse(&format!(
+            r#"{BASE}
+            [radio]
+            center_freq = 156_800_000
+            sample_rate = 250_000
+            tuner_gain = 10
+
+            [auth]
+            tokens = [{{ name = "north-harbour", hash = "{}", role = "ingest" }}]"#,
+            "0".repeat(64)
+        ))
+        .unwrap();
+        config.server.ingest = true;
+        assert!(!config.validate().contains(&problem));
+
+        config.auth = None;
+        assert!(config.validate().contains(&problem));
+    }
+
+    #[test]
+    fn saving_channels_keeps_comments() {
+        let original = format!(
+            r#"{BASE}
+            [radio]
+            center_freq = 156_800_000
+            sample_rate = 250_000
+            tuner_gain = 10
+
+            [[channels]]
+            name = "Marine 9"
+            freq = 156_450_000
+            squelch = 0.01
+            gain = 10.0
+            mode = "fm" # or "am"
+            # tone = {{ ctcss = 100.0 }}
+            priority = false"#
+        );
+        let path = env::temp_dir().join(format!("radio-history-{}.toml", Uuid::new_v4()));
+        fs::write(&path, &original).unwrap();
+
+        let mut config = Config::parse(&original).unwrap();
+        config.channels.remove(1);
+        config.channels[1].gain = 5.0;
+        config.channels.push(ChannelConfig {
+            name: "Marine 68".into(),
+            freq: 156_425_000,
+            ..config.channels[0].clone()
+        });
+        config.save_channels(&path).unwrap();
+
+        let saved = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(saved.contains(r#"mode = "fm" # or "am""#), "{saved}");
+        assert!(saved.contains("# tone = { ctcss = 100.0 }"), "{saved}");
+        assert!(saved.contains("freq = 156_450_000"), "{saved}");
+        assert!(saved.contains("gain = 5.0"), "{saved}");
+        assert!(!saved.contains("Guard"), "{saved}");
+
+        let saved = Config::parse(&saved).unwrap();
+        let names = saved
+            .channels
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Marine 16", "Marine 9", "Marine 68"]);
     }
 }
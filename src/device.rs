@@ -0,0 +1,260 @@
+use std::{
+    sync::{atomic::Ordering, Arc},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clone_macro::clone;
+use flume::{Receiver, RecvTimeoutError, Sender, TrySendError};
+use log::debug;
+use rtlsdr::{RTLSDRDevice, RTLSDRError};
+
+use crate::{config::RadioConfig, consts::BUFFER_SIZE, metrics::RadioMetrics};
+
+/// Waiting this long for samples means the device has stopped sending them.
+const STALL_TIMEOUT: Duration = Duration::from_secs(5);
+/// Buffers held while processing falls behind, after which new ones are dropped.
+const QUEUED_BUFFERS: usize = 4;
+
+/// An RTL-SDR, read on its own thread so a read that never returns can be noticed.
+/// Its settings are kept to be replayed when it's reopened.
+pub struct Device {
+    index: i32,
+    /// Used to find the device again if replugging it changed its index.
+    /// Left out when other devices share it, as many cheap ones do.
+    serial: Option<String>,
+    settings: Settings,
+    /// Counts the samples dropped when processing falls behind.
+    metrics: Arc<RadioMetrics>,
+    /// Tuner gains the device supports, in tenths of a dB.
+    gains: Vec<i32>,
+    commands: Sender<Command>,
+    samples: Receiver<Result<Samples>>,
+    /// Disconnected once the reader thread has closed the device.
+    closed: Receiver<()>,
+}
+
+#[derive(Clone, Copy)]
+pub struct Settings {
+    pub center_freq: u32,
+    pub sample_rate: u32,
+    /// In tenths of a dB.
+    pub tuner_gain: i32,
+}
+
+pub struct Samples {
+    /// Interleaved 8 bit I and Q values.
+    pub data: Vec<u8>,
+    /// What the device was tuned to when they were read.
+    pub center_freq: u32,
+    /// Read right after a retune, so they may be from partway through it.
+    pub settling: bool,
+}
+
+enum Command {
+    CenterFreq(u32),
+    TunerGain(i32),
+}
+
+/// What the reader thread found out about the device after opening it.
+struct Opened {
+    serial: Option<String>,
+    gains: Vec<i32>,
+}
+
+impl Device {
+    pub fn open(index: i32, settings: Settings, metrics: Arc<RadioMetrics>) -> Result<Self> {
+        let (commands, command_rx) = flume::unbounded();
+        let (samples_tx, samples) = flume::bounded(QUEUED_BUFFERS);
+        let (opened_tx, opened_rx) = flume::bounded(1);
+        let (closed_tx, closed) = flume::bounded::<()>(0);
+
+        thread::Builder::new()
+            .name(format!("rtlsdr-{index}"))
+            .spawn(clone!([metrics], move || {
+                let _closed = closed_tx;
+                let device = match configure(index, settings) {
+                    Ok((device, opened)) => {
+                        let _ = opened_tx.send(Ok(opened));
+                        device
+                    }
+                    Err(err) => {
+                        let _ = opened_tx.send(Err(err));
+                        return;
+                    }
+                };
+
+                let center_freq = settings.center_freq;
+                if let Err(err) = read(device, center_freq, command_rx, &samples_tx, &metrics) {
+                    let _ = samples_tx.send(Err(err));
+                }
+            }))?;
+
+        let opened = match opened_rx.recv_timeout(STALL_TIMEOUT) {
+            Ok(opened) => opened,
+            Err(_) => Err(anyhow!("timed out")),
+        }
+        .with_context(|| format!("opening RTL-SDR #{index}"))?;
+
+        Ok(Self {
+            index,
+            serial: opened
+                .serial
+                .filter(|serial| list().iter().filter(|x| &x.serial == serial).count() == 1),
+            settings,
+            metrics,
+            gains: opened.gains,
+            commands,
+            samples,
+            closed,
+        })
+    }
+
+    /// Closes the device and opens it again with the same settings.
+    pub fn reopen(&mut self) -> Result<()> {
+        // Dropping the receiver stops the reader thread after its current read, unless it's stuck
+        self.samples = flume::bounded(0).1;
+        if self.closed.recv_timeout(STALL_TIMEOUT) == Err(RecvTimeoutError::Timeout) {
+            debug!("Previous reader for RTL-SDR #{} is stuck", self.index);
+        }
+
+        if let Some(serial) = &self.serial {
+            match rtlsdr::get_index_by_serial(serial.to_owned()) {
+                Ok(index) => self.index = index,
+                Err(err) => bail!("finding RTL-SDR with serial `{serial}`: {err}"),
+            }
+        }
+
+        *self = Self::open(self.index, self.settings, self.metrics.clone())?;
+        Ok(())
+    }
+
+    /// Waits for the next buffer, failing if the device has stopped working.
+    pub fn read(&self) -> Result<Samples> {
+        match self.samples.recv_timeout(STALL_TIMEOUT) {
+            Ok(samples) => samples,
+            Err(RecvTimeoutError::Timeout) => bail!("No samples for {STALL_TIMEOUT:?}"),
+            Err(RecvTimeoutError::Disconnected) => bail!("Reader thread stopped"),
+        }
+    }
+
+    /// Takes effect from the next buffer that's read. Errors are returned from [`Device::read`].
+    pub fn set_center_freq(&mut self, freq: u32) {
+        self.settings.center_freq = freq;
+        let _ = self.commands.send(Command::CenterFreq(freq));
+    }
+
+    /// In tenths of a dB. Errors are returned from [`Device::read`].
+    pub fn set_tuner_gain(&mut self, gain: i32) {
+        self.settings.tuner_gain = gain;
+        let _ = self.commands.send(Command::TunerGain(gain));
+    }
+
+    /// In tenths of a dB, as last set.
+    pub fn tuner_gain(&self) -> i32 {
+        self.settings.tuner_gain
+    }
+
+    pub fn tuner_gains(&self) -> &[i32] {
+        &self.gains
+    }
+}
+
+/// A connected device, as listed by `radio-history devices`.
+pub struct Info {
+    pub index: i32,
+    pub name: String,
+    pub serial: String,
+}
+
+/// Finds the index of the device a radio is set up to use.
+pub fn find(radio: &RadioConfig) -> Result<i32> {
+    match &radio.serial {
+        Some(serial) => rtlsdr::get_index_by_serial(serial.to_owned())
+            .map_err(|err| anyhow!("finding RTL-SDR with serial `{serial}`: {err}")),
+        None => Ok(radio.device_index.unwrap_or(0)),
+    }
+}
+
+/// Lists the connected devices.
+pub fn list() -> Vec<Info> {
+    (0..rtlsdr::get_device_count())
+        .map(|index| Info {
+            index,
+            name: rtlsdr::get_device_name(index),
+            serial: rtlsdr::get_device_usb_strings(index)
+                .map(|x| x.serial)
+                .unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Opens the device and applies the settings, on the reader thread.
+fn configure(index: i32, settings: Settings) -> Result<(RTLSDRDevice, Opened)> {
+    let mut device = rtlsdr::open(index).map_err(error)?;
+    device
+        .set_center_freq(settings.center_freq)
+        .map_err(error)?;
+    device
+        .set_sample_rate(settings.sample_rate)
+        .map_err(error)?;
+    device.set_tuner_gain_mode(true).map_err(error)?;
+    device.set_agc_mode(false).map_err(error)?;
+    device.set_tuner_gain(settings.tuner_gain).map_err(error)?;
+    device.reset_buffer().map_err(error)?;
+
+    let opened = Opened {
+        serial: device
+            .get_usb_strings()
+            .ok()
+            .map(|x| x.serial)
+            .filter(|x| !x.is_empty()),
+        gains: device.get_tuner_gains().map_err(error)?,
+    };
+    Ok((device, opened))
+}
+
+/// Reads until the device fails or the [`Device`] is dropped, applying commands between reads.
+/// Buffers are dropped rather than queued when processing falls behind.
+fn read(
+    mut device: RTLSDRDevice,
+    mut center_freq: u32,
+    commands: Receiver<Command>,
+    samples: &Sender<Result<Samples>>,
+    metrics: &RadioMetrics,
+) -> Result<()> {
+    loop {
+        let mut settling = false;
+        for command in commands.try_iter() {
+            match command {
+                Command::CenterFreq(freq) => {
+                    device.set_center_freq(freq).map_err(error)?;
+                    center_freq = freq;
+                    settling = true;
+                }
+                Command::TunerGain(gain) => device.set_tuner_gain(gain).map_err(error)?,
+            }
+        }
+
+        let data = device.read_sync(BUFFER_SIZE).map_err(error)?;
+        let buffer = Samples {
+            data,
+            center_freq,
+            settling,
+        };
+        match samples.try_send(Ok(buffer)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(Ok(buffer))) => {
+                let count = buffer.data.len() as u64 / 2;
+                metrics.samples_dropped.fetch_add(count, Ordering::Relaxed);
+            }
+            Err(_) => return Ok(()),
+        }
+    }
+}
+
+/// `RTLSDRError` doesn't implement `std::error::Error`.
+fn error(err: RTLSDRError) -> anyhow::Error {
+    anyhow!("{err}")
+}
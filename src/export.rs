@@ -0,0 +1,210 @@
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{bail, Context, Result};
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::web::database::{Filter, Message};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Jsonl,
+    /// The messages' audio, with a manifest and a CSV of the messages.
+    Zip,
+}
+
+/// Describes the bundle, so it can be checked against the audio it came with.
+#[derive(Serialize)]
+struct Manifest<'a> {
+    exported: NaiveDateTime,
+    filter: &'a Filter,
+    messages: Vec<ManifestEntry<'a>>,
+}
+
+#[derive(Serialize)]
+struct ManifestEntry<'a> {
+    #[serde(flatten)]
+    message: &'a Message,
+    /// Path of the audio in the bundle, missing if the file was deleted.
+    file: Option<String>,
+    /// SHA-256 of the audio, in hex.
+    sha256: Option<String>,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Jsonl => "jsonl",
+            Format::Zip => "zip",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv",
+            Format::Jsonl => "application/jsonl",
+            Format::Zip => "application/zip",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "csv" => Format::Csv,
+            "jsonl" => Format::Jsonl,
+            "zip" => Format::Zip,
+            _ => bail!("Unknown export format `{s}`, expected csv, jsonl or zip"),
+        })
+    }
+}
+
+impl Filter {
+    /// Builds a filter from user input. A bare date for `to` includes that whole day.
+    pub fn parse(from: Option<&str>, to: Option<&str>, channel: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            from: from.map(|x| parse_date(x, false)).transpose()?,
+            to: to.map(|x| parse_date(x, true)).transpose()?,
+            channel: channel.map(str::to_owned),
+        })
+    }
+}
+
+/// Accepts `2024-05-01`, `2024-05-01 13:00` or `2024-05-01T13:00:00`.
+fn parse_date(value: &str, end_of_day: bool) -> Result<NaiveDateTime> {
+    let normalized = value.trim().replace('T', " ");
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(&normalized, format) {
+            return Ok(date);
+        }
+    }
+
+    let date = NaiveDate::parse_from_str(&normalized, "%Y-%m-%d")
+        .with_context(|| format!("Invalid date `{value}`, expected YYYY-MM-DD [HH:MM[:SS]]"))?;
+    let date = date.and_hms_opt(0, 0, 0).unwrap();
+    Ok(match end_of_day {
+        true => date + TimeDelta::days(1),
+        false => date,
+    })
+}
+
+pub fn write(
+    format: Format,
+    messages: &[Message],
+    filter: &Filter,
+    data_dir: &Path,
+    out: impl Write,
+) -> Result<()> {
+    match format {
+        Format::Csv => write_csv(messages, out),
+        Format::Jsonl => write_jsonl(messages, out),
+        Format::Zip => write_zip(messages, filter, data_dir, out),
+    }
+}
+
+fn write_csv(messages: &[Message], mut out: impl Write) -> Result<()> {
+    writeln!(out, "date,station,channel,tone,text,audio,previous")?;
+    for message in messages {
+        let fields = [
+            Some(message.date.to_string()),
+            message.station.clone(),
+            message.channel.clone(),
+            message.tone.clone(),
+            message.text.clone(),
+            Some(message.audio.to_string()),
+            message.previous.map(|x| x.to_string()),
+        ]
+        .map(|x| csv_field(&x.unwrap_or_default()));
+        writeln!(out, "{}", fields.join(","))?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+fn write_jsonl(messages: &[Message], mut out: impl Write) -> Result<()> {
+    for message in messages {
+        writeln!(out, "{}", json!(message))?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes the bundle without seeking, so it can be streamed straight to a client.
+fn write_zip(
+    messages: &[Message],
+    filter: &Filter,
+    data_dir: &Path,
+    out: impl Write,
+) -> Result<()> {
+    let mut zip = ZipWriter::new_stream(out);
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .large_file(true);
+
+    let mut entries = Vec::new();
+    for message in messages {
+        let path = data_dir
+            .join("audio")
+            .join(format!("{}.wav", message.audio));
+        let audio = match fs::read(&path) {
+            Ok(audio) => Some(audio),
+            Err(err) if err.kind() == ErrorKind::NotFound => None,
+            Err(err) => return Err(err).context(format!("reading `{}`", path.display())),
+        };
+
+        let mut entry = ManifestEntry {
+            message,
+            file: None,
+            sha256: None,
+        };
+        if let Some(audio) = audio {
+            let file = format!("audio/{}.wav", message.audio);
+            let modified = message.date.try_into().unwrap_or_default();
+            zip.start_file(&file, options.last_modified_time(modified))?;
+            zip.write_all(&audio)?;
+
+            entry.sha256 = Some(format!("{:x}", Sha256::digest(&audio)));
+            entry.file = Some(file);
+        }
+        entries.push(entry);
+    }
+
+    let mut csv = Vec::new();
+    write_csv(messages, &mut csv)?;
+    zip.start_file("messages.csv", options)?;
+    zip.write_all(&csv)?;
+
+    let manifest = Manifest {
+        exported: Utc::now().naive_utc(),
+        filter,
+        messages: entries,
+    };
+    zip.start_file("manifest.json", options)?;
+    serde_json::to_writer_pretty(&mut zip, &manifest)?;
+
+    zip.finish()?.flush()?;
+    Ok(())
+}
+
+/// Quotes a field if it contains anything that would break the row up.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
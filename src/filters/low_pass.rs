@@ -44,7 +44,9 @@ where
         cutoff_freq: f32,
     ) -> impl Iterator<Item = Complex<f32>> {
         let mut filter = LowPassFilter::new(sample_rate, cutoff_freq);
-        filter.prime(self.next().unwrap());
+        if let Some(first) = self.next() {
+            filter.prime(first);
+        }
         self.scan(filter, |filter, value| Some(filter.filter(value)))
     }
 }
@@ -55,7 +57,9 @@ where
 {
     fn low_pass(mut self, sample_rate: u32, cutoff_freq: f32) -> impl Iterator<Item = f32> {
         let mut filter = LowPassFilter::new(sample_rate, cutoff_freq);
-        filter.prime(Complex::new(self.next().unwrap(), 0.0));
+        if let Some(first) = self.next() {
+            filter.prime(Complex::new(first, 0.0));
+        }
         self.map(move |value| filter.filter(Complex::new(value, 0.0)).re)
     }
 }
@@ -2,28 +2,25 @@ use std::f32::consts::TAU;
 
 use num_complex::Complex;
 
+/// Shifts a signal at `offset_freq` (which may be negative) down to 0 Hz.
 pub struct OffsetFilter {
-    offset_angle: f32,
-
-    sample: u32,
-    sample_rate: u32,
+    /// Phase change per sample, in radians.
+    step: f32,
+    phase: f32,
 }
 
 impl OffsetFilter {
     pub fn new(offset_freq: f32, sample_rate: u32) -> Self {
         Self {
-            offset_angle: TAU * offset_freq,
-            sample: 0,
-            sample_rate,
+            step: -TAU * offset_freq / sample_rate as f32,
+            phase: 0.0,
         }
     }
 
     pub fn filter(&mut self, iq: Complex<f32>) -> Complex<f32> {
-        let t = self.sample as f32 / self.sample_rate as f32;
-        let angle = self.offset_angle * t * Complex::i();
-
-        self.sample += 1;
-        iq * angle.exp()
+        let out = iq * Complex::from_polar(1.0, self.phase);
+        self.phase = (self.phase + self.step) % TAU;
+        out
     }
 }
 
@@ -0,0 +1,227 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Arguments,
+    io::{self, Write},
+};
+
+use afire::trace::{self, Formatter};
+use chrono::Utc;
+use log::{
+    kv::{self, Key, Value, VisitSource},
+    LevelFilter, Log, Metadata, Record,
+};
+use parking_lot::RwLock;
+use serde::Deserialize;
+use serde_json::{json, Map};
+
+use crate::config::LogConfig;
+
+static LOGGER: Logger = Logger {
+    filter: RwLock::new(Filter {
+        level: LevelFilter::Info,
+        targets: BTreeMap::new(),
+    }),
+    format: RwLock::new(Format::Text),
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Off,
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// `2024-05-01T13:00:00.000Z INFO radio_history::app: message key=value`
+    #[default]
+    Text,
+    /// One JSON object per line, with the key-values as fields.
+    Json,
+}
+
+struct Logger {
+    filter: RwLock<Filter>,
+    format: RwLock<Format>,
+}
+
+struct Filter {
+    level: LevelFilter,
+    /// Levels for targets and everything under them, like `radio_history::web`.
+    targets: BTreeMap<String, LevelFilter>,
+}
+
+/// Sends afire's logs through ours, under the `afire` target.
+struct AfireFormatter;
+
+/// Installs the logger. Can be called again to apply a new config.
+pub fn init(config: &LogConfig) {
+    *LOGGER.filter.write() = Filter {
+        level: config.level.into(),
+        targets: config
+            .targets
+            .iter()
+            .map(|(target, level)| (target.to_owned(), (*level).into()))
+            .collect(),
+    };
+    *LOGGER.format.write() = config.format;
+
+    let max = config
+        .targets
+        .values()
+        .map(|&x| LevelFilter::from(x))
+        .fold(config.level.into(), Ord::max);
+    log::set_max_level(max);
+
+    if log::set_logger(&LOGGER).is_ok() {
+        trace::set_log_formatter(AfireFormatter);
+        trace::set_log_level(trace::Level::Trace);
+    }
+}
+
+impl Filter {
+    /// The most specific configured target wins.
+    fn level(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .filter(|(prefix, _)| {
+                target
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.level, |(_, level)| *level)
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.read().level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let time = Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ");
+        let line = match *self.format.read() {
+            Format::Text => {
+                let mut fields = Text(String::new());
+                let _ = record.key_values().visit(&mut fields);
+                format!(
+                    "{time} {:<5} {}: {}{}",
+                    record.level(),
+                    record.target(),
+                    record.args(),
+                    fields.0
+                )
+            }
+            Format::Json => {
+                let mut fields = Json(Map::new());
+                let _ = record.key_values().visit(&mut fields);
+                let mut line = json!({
+                    "time": time.to_string(),
+                    "level": record.level().as_str(),
+                    "target": record.target(),
+                    "message": record.args().to_string(),
+                });
+                line.as_object_mut().unwrap().extend(fields.0);
+                line.to_string()
+            }
+        };
+
+        let _ = writeln!(io::stderr().lock(), "{line}");
+    }
+
+    fn flush(&self) {
+        let _ = io::stderr().flush();
+    }
+}
+
+struct Text(String);
+
+impl<'kvs> VisitSource<'kvs> for Text {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let value = value.to_string();
+        // Quote values that would otherwise run into the next field
+        if value.is_empty() || value.contains([' ', '"', '=']) {
+            self.0.push_str(&format!(" {key}={value:?}"));
+        } else {
+            self.0.push_str(&format!(" {key}={value}"));
+        }
+        Ok(())
+    }
+}
+
+struct Json(Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for Json {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let value = match (value.to_i64(), value.to_f64(), value.to_bool()) {
+            (Some(x), _, _) => json!(x),
+            (_, Some(x), _) => json!(x),
+            (_, _, Some(x)) => json!(x),
+            _ => json!(value.to_string()),
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+impl Formatter for AfireFormatter {
+    fn format(&self, level: trace::Level, _color: bool, msg: Arguments) {
+        let level = match level {
+            trace::Level::Off => return,
+            trace::Level::Error => log::Level::Error,
+            trace::Level::Trace => log::Level::Info,
+            trace::Level::Debug => log::Level::Trace,
+        };
+        log::log!(target: "afire", level, "{msg}");
+    }
+}
+
+impl From<Level> for LevelFilter {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Off => LevelFilter::Off,
+            Level::Error => LevelFilter::Error,
+            Level::Warn => LevelFilter::Warn,
+            Level::Info => LevelFilter::Info,
+            Level::Debug => LevelFilter::Debug,
+            Level::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use log::LevelFilter;
+
+    use super::Filter;
+
+    #[test]
+    fn most_specific_target_wins() {
+        let filter = Filter {
+            level: LevelFilter::Info,
+            targets: BTreeMap::from([
+                ("radio_history::web".into(), LevelFilter::Warn),
+                ("radio_history::web::auth".into(), LevelFilter::Debug),
+            ]),
+        };
+
+        assert_eq!(filter.level("radio_history::app"), LevelFilter::Info);
+        assert_eq!(filter.level("radio_history::web"), LevelFilter::Warn);
+        assert_eq!(filter.level("radio_history::web::live"), LevelFilter::Warn);
+        assert_eq!(filter.level("radio_history::web::auth"), LevelFilter::Debug);
+        assert_eq!(filter.level("radio_history::webhooks"), LevelFilter::Info);
+    }
+}
@@ -1,21 +1,93 @@
+use std::{env, thread};
+
 use anyhow::Result;
 
+mod alerts;
 mod app;
+mod archive;
+mod cli;
 mod config;
 mod consts;
+mod device;
+mod export;
 mod filters;
+mod logger;
+mod metrics;
 mod misc;
+mod mqtt;
+mod reload;
+mod scanner;
 mod signal;
+mod sync;
+mod transcription;
 mod web;
-use app::App;
+use app::{App, Shared};
 use config::Config;
+use reload::Reloader;
+
+const CONFIG_PATH: &str = "config.toml";
 
 fn main() -> Result<()> {
-    let config = Config::load("config.toml")?;
-    let mut radio = App::new(config)?;
-    radio.init_device();
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        return cli::run(&args);
+    }
+
+    let config = Config::load_startup(CONFIG_PATH)?;
+    logger::init(&config.log);
+    let reloader = Reloader::new(CONFIG_PATH, config.clone());
+
+    // Some platforms can only open windows from the main thread, so the radio gets its own
+    #[cfg(feature = "debug")]
+    {
+        let (tx, rx) = flume::unbounded();
+        let radio = std::thread::spawn(move || {
+            if let Err(err) = run(config, reloader, tx) {
+                log::error!("{err:?}");
+                std::process::exit(1);
+            }
+        });
+
+        signal::debug::start(rx)?;
+        radio.join().unwrap();
+        Ok(())
+    }
+
+    #[cfg(not(feature = "debug"))]
+    run(config, reloader)
+}
+
+fn run(
+    config: Config,
+    reloader: Reloader,
+    #[cfg(feature = "debug")] debug: flume::Sender<signal::debug::Frame>,
+) -> Result<()> {
+    let shared = Shared::new(&config, reloader.clone())?;
+    let mut radios = Vec::new();
+    for idx in 0..config.radios.len() {
+        // The viewer only shows the first radio
+        #[cfg(feature = "debug")]
+        let debug = match idx {
+            0 => debug.clone(),
+            _ => flume::unbounded().0,
+        };
+        radios.push(App::new(
+            config.clone(),
+            idx,
+            &shared,
+            &reloader,
+            #[cfg(feature = "debug")]
+            debug,
+        )?);
+    }
+    reloader.watch();
 
-    loop {
-        radio.process_samples();
+    // Validation makes sure there's at least one radio, and the last one gets this thread
+    let mut last = radios.pop().unwrap();
+    for mut radio in radios {
+        thread::Builder::new()
+            .name(format!("radio-{}", radio.name()))
+            .spawn(move || radio.run())?;
     }
+    last.run()
 }
@@ -0,0 +1,265 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicI32, AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use parking_lot::Mutex;
+
+/// Upper bounds of the transcription duration buckets, in seconds.
+const DURATION_BUCKETS: [f64; 10] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0];
+
+#[derive(Default)]
+pub struct Metrics {
+    /// Each radio's metrics, by name.
+    radios: Mutex<BTreeMap<String, Arc<RadioMetrics>>>,
+    /// Recordings thrown away for being shorter than their channel's `min_duration`.
+    pub discarded_messages: AtomicU64,
+    /// Recordings saved on each channel, by name.
+    recordings: Mutex<BTreeMap<String, u64>>,
+    /// Seconds taken to transcribe each recording.
+    pub transcription: Histogram,
+    /// Recordings being transcribed or queued to be.
+    pub transcriptions_pending: AtomicU64,
+}
+
+#[derive(Default)]
+pub struct RadioMetrics {
+    /// IQ samples read from the device.
+    pub samples_read: AtomicU64,
+    /// Samples thrown away after a retune or when processing fell behind, or missing from a
+    /// short read.
+    pub samples_dropped: AtomicU64,
+    pub buffers_processed: AtomicU64,
+    /// Times the device failed or stalled and had to be reopened.
+    pub device_failures: AtomicU64,
+    /// Current tuner gain in tenths of a dB.
+    pub tuner_gain: AtomicI32,
+}
+
+/// A Prometheus style histogram with cumulative buckets.
+pub struct Histogram {
+    buckets: &'static [f64],
+    counts: Vec<AtomicU64>,
+    /// Bits of the `f64` sum of every observation.
+    sum: AtomicU64,
+    count: AtomicU64,
+}
+
+/// Builds a response in Prometheus' text exposition format, with every name prefixed by
+/// `radio_history_`.
+#[derive(Default)]
+pub struct Exposition(String);
+
+impl Metrics {
+    /// The metrics for a radio, labeled with its name.
+    pub fn radio(&self, name: &str) -> Arc<RadioMetrics> {
+        self.radios
+            .lock()
+            .entry(name.to_owned())
+            .or_default()
+            .clone()
+    }
+
+    pub fn recorded(&self, channel: &str) {
+        *self
+            .recordings
+            .lock()
+            .entry(channel.to_owned())
+            .or_default() += 1;
+    }
+
+    pub fn write(&self, out: &mut Exposition) {
+        let radios = self.radios.lock();
+        let mut radio = |name, kind, help, value: fn(&RadioMetrics) -> f64| {
+            let values = radios.iter().map(|(k, v)| (k.as_str(), value(v)));
+            out.labeled(name, kind, help, "radio", values);
+        };
+        radio(
+            "samples_read_total",
+            "counter",
+            "IQ samples read from each device.",
+            |x| counter(&x.samples_read),
+        );
+        radio(
+            "samples_dropped_total",
+            "counter",
+            "IQ samples thrown away after a retune or when processing fell behind, or missing from short reads.",
+            |x| counter(&x.samples_dropped),
+        );
+        radio(
+            "buffers_processed_total",
+            "counter",
+            "Sample buffers demodulated.",
+            |x| counter(&x.buffers_processed),
+        );
+        radio(
+            "device_failures_total",
+            "counter",
+            "Times each device failed or stalled and was reopened.",
+            |x| counter(&x.device_failures),
+        );
+        radio(
+            "tuner_gain_db",
+            "gauge",
+            "Current tuner gain of each device.",
+            |x| x.tuner_gain.load(Ordering::Relaxed) as f64 / 10.0,
+        );
+        drop(radios);
+
+        out.metric(
+            "discarded_messages_total",
+            "counter",
+            "Recordings thrown away for being shorter than min_duration.",
+            counter(&self.discarded_messages),
+        );
+
+        let recordings = self.recordings.lock();
+        out.labeled(
+            "recordings_total",
+            "counter",
+            "Recordings saved on each channel.",
+            "channel",
+            recordings.iter().map(|(k, v)| (k.as_str(), *v as f64)),
+        );
+        drop(recordings);
+
+        out.histogram(
+            "transcription_duration_seconds",
+            "Time taken to transcribe a recording.",
+            &self.transcription,
+        );
+        out.metric(
+            "transcriptions_pending",
+            "gauge",
+            "Recordings being transcribed or waiting to be.",
+            counter(&self.transcriptions_pending),
+        );
+    }
+}
+
+impl Histogram {
+    pub fn new(buckets: &'static [f64]) -> Self {
+        Self {
+            buckets,
+            counts: buckets.iter().map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0f64.to_bits()),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        for (bound, count) in self.buckets.iter().zip(&self.counts) {
+            if value <= *bound {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let _ = self
+            .sum
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| {
+                Some((f64::from_bits(x) + value).to_bits())
+            });
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new(&DURATION_BUCKETS)
+    }
+}
+
+impl Exposition {
+    pub fn metric(&mut self, name: &str, kind: &str, help: &str, value: f64) {
+        self.header(name, kind, help);
+        let _ = writeln!(self.0, "radio_history_{name} {value}");
+    }
+
+    pub fn labeled<'a>(
+        &mut self,
+        name: &str,
+        kind: &str,
+        help: &str,
+        label: &str,
+        values: impl IntoIterator<Item = (&'a str, f64)>,
+    ) {
+        self.header(name, kind, help);
+        for (key, value) in values {
+            let key = escape(key);
+            let _ = writeln!(self.0, "radio_history_{name}{{{label}=\"{key}\"}} {value}");
+        }
+    }
+
+    pub fn histogram(&mut self, name: &str, help: &str, histogram: &Histogram) {
+        self.header(name, "histogram", help);
+        let count = histogram.count.load(Ordering::Relaxed);
+        for (bound, bucket) in histogram.buckets.iter().zip(&histogram.counts) {
+            let bucket = bucket.load(Ordering::Relaxed);
+            let _ = writeln!(
+                self.0,
+                "radio_history_{name}_bucket{{le=\"{bound}\"}} {bucket}"
+            );
+        }
+
+        let sum = f64::from_bits(histogram.sum.load(Ordering::Relaxed));
+        let _ = writeln!(self.0, "radio_history_{name}_bucket{{le=\"+Inf\"}} {count}");
+        let _ = writeln!(self.0, "radio_history_{name}_sum {sum}");
+        let _ = writeln!(self.0, "radio_history_{name}_count {count}");
+    }
+
+    fn header(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.0, "# HELP radio_history_{name} {help}");
+        let _ = writeln!(self.0, "# TYPE radio_history_{name} {kind}");
+    }
+
+    pub fn finish(self) -> String {
+        self.0
+    }
+}
+
+fn counter(value: &AtomicU64) -> f64 {
+    value.load(Ordering::Relaxed) as f64
+}
+
+/// Escapes a label value, which is quoted.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Exposition, Histogram};
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let histogram = Histogram::new(&[1.0, 5.0]);
+        for value in [0.5, 2.0, 2.5, 10.0] {
+            histogram.observe(value);
+        }
+
+        let mut out = Exposition::default();
+        out.histogram("test", "Test.", &histogram);
+        let out = out.finish();
+        assert!(out.contains("radio_history_test_bucket{le=\"1\"} 1\n"));
+        assert!(out.contains("radio_history_test_bucket{le=\"5\"} 3\n"));
+        assert!(out.contains("radio_history_test_bucket{le=\"+Inf\"} 4\n"));
+        assert!(out.contains("radio_history_test_sum 15\n"));
+        assert!(out.contains("radio_history_test_count 4\n"));
+    }
+
+    #[test]
+    fn labels_are_escaped() {
+        let mut out = Exposition::default();
+        out.labeled("test", "counter", "Test.", "channel", [("a \"b\"", 1.0)]);
+        assert!(out
+            .finish()
+            .contains("radio_history_test{channel=\"a \\\"b\\\"\"} 1\n"));
+    }
+}
@@ -1,6 +1,7 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use chrono::{DateTime, NaiveDateTime};
+use serde::Serializer;
 
 pub fn date_time() -> NaiveDateTime {
     DateTime::from_timestamp(
@@ -13,3 +14,8 @@ pub fn date_time() -> NaiveDateTime {
     .unwrap()
     .naive_local()
 }
+
+/// Serializes through the shortest decimal form, so `0.1` isn't written out as `0.10000000149011612`.
+pub fn serialize_f32<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(value.to_string().parse().unwrap())
+}
@@ -0,0 +1,139 @@
+use std::{thread, time::Duration};
+
+use flume::Sender;
+use log::{info, warn};
+use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS};
+use serde_json::json;
+
+use crate::{config::MqttConfig, web::UiMessage};
+
+/// Publishes UI events to an MQTT broker under `{prefix}/`:
+/// - `status`: `online` or `offline`, retained
+/// - `events`: every event except the periodic levels
+/// - `messages`: completed messages
+/// - `alerts`: triggered alerts
+/// - `channels/{name}`: each channel's current state, retained
+/// - `gain/{radio}`: each radio's tuner gain, retained
+/// - `device/{radio}`: if each radio's device is working, retained
+pub fn start(config: MqttConfig) -> Sender<UiMessage> {
+    let status = format!("{}/status", config.prefix);
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options
+        .set_keep_alive(Duration::from_secs(30))
+        .set_last_will(LastWill::new(&status, "offline", QoS::AtLeastOnce, true));
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.clone().unwrap_or_default());
+    }
+
+    let (client, mut connection) = Client::new(options, 256);
+
+    let publisher = client.clone();
+    thread::spawn(move || {
+        for event in connection.iter() {
+            match event {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    info!(host = config.host.as_str(), port = config.port; "Connected to MQTT broker");
+                    let _ = client.try_publish(&status, QoS::AtLeastOnce, true, "online");
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    warn!("MQTT connection failed, retrying in 5s: {err}");
+                    thread::sleep(Duration::from_secs(5));
+                }
+            }
+        }
+    });
+
+    let (tx, rx) = flume::unbounded::<UiMessage>();
+    thread::spawn(move || {
+        for message in rx.iter() {
+            for (topic, retain, payload) in publications(&message) {
+                // Only fails if the queue is full while the broker is unreachable,
+                // in which case live events aren't worth holding on to
+                let topic = format!("{}/{topic}", config.prefix);
+                let _ = publisher.try_publish(topic, QoS::AtLeastOnce, retain, payload);
+            }
+        }
+    });
+
+    tx
+}
+
+/// The topics, relative to the prefix, a message is published to, and if they're retained.
+fn publications(message: &UiMessage) -> Vec<(String, bool, String)> {
+    let payload = json!(message).to_string();
+    let state = |name: &str, state: &str, uuid| {
+        let topic = format!("channels/{}", topic_level(name));
+        let payload = json!({ "state": state, "uuid": uuid }).to_string();
+        (topic, true, payload)
+    };
+
+    let mut out = Vec::new();
+    match message {
+        UiMessage::Level { .. } => return out,
+        UiMessage::Receiving { name, uuid, .. } => out.push(state(name, "receiving", Some(uuid))),
+        UiMessage::Processing { name, uuid, .. } => out.push(state(name, "processing", Some(uuid))),
+        UiMessage::Discarded { name, .. } => out.push(state(name, "idle", None)),
+        UiMessage::Complete { name, message, .. } => {
+            out.push(state(name, "idle", None));
+            out.push(("messages".into(), false, json!(message).to_string()));
+        }
+        UiMessage::Alert { .. } => out.push(("alerts".into(), false, payload.clone())),
+        UiMessage::TunerGain { radio, gain } => {
+            let topic = format!("gain/{}", topic_level(radio));
+            out.push((topic, true, json!(gain).to_string()));
+        }
+        UiMessage::Device { radio, .. } => out.push((
+            format!("device/{}", topic_level(radio)),
+            true,
+            payload.clone(),
+        )),
+    }
+
+    out.push(("events".into(), false, payload));
+    out
+}
+
+/// Makes a channel or radio name safe to use as a single topic level.
+fn topic_level(name: &str) -> String {
+    name.replace(['/', '+', '#'], "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::publications;
+    use crate::web::UiMessage;
+
+    #[test]
+    fn channel_state_is_retained() {
+        let uuid = Uuid::new_v4();
+        let message = UiMessage::Receiving {
+            idx: 0,
+            name: "Marine 16/A".into(),
+            uuid,
+        };
+
+        let publications = publications(&message);
+        let (topic, retain, payload) = &publications[0];
+        assert_eq!(topic, "channels/Marine 16_A");
+        assert!(retain);
+        assert!(payload.contains(&uuid.to_string()));
+        assert_eq!(publications[1].0, "events");
+        assert!(!publications[1].1);
+    }
+
+    #[test]
+    fn levels_are_not_published() {
+        let message = UiMessage::Level {
+            idx: 0,
+            name: "Marine 16".into(),
+            uuid: None,
+            rms: 0.0,
+            noise_floor: 0.0,
+            open: false,
+        };
+        assert!(publications(&message).is_empty());
+    }
+}
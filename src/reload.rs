@@ -0,0 +1,130 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use clone_macro::clone;
+use flume::{Receiver, Sender};
+use log::{error, info, warn};
+use parking_lot::Mutex;
+
+use crate::config::Config;
+
+/// How often the config file is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Loads new configs from disk and hands the valid ones to each running [`crate::app::App`].
+#[derive(Clone)]
+pub struct Reloader {
+    path: Arc<PathBuf>,
+    /// Modification time of the config file when it was last loaded or saved.
+    /// Also held while saving, so the watcher doesn't pick up our own writes.
+    modified: Arc<Mutex<Option<SystemTime>>>,
+    /// The last config that loaded, which is what's running.
+    current: Arc<Mutex<Config>>,
+    subscribers: Arc<Mutex<Vec<Sender<Config>>>>,
+}
+
+impl Reloader {
+    pub fn new(path: impl AsRef<Path>, config: Config) -> Self {
+        Self {
+            path: Arc::new(path.as_ref().to_owned()),
+            modified: Arc::new(Mutex::new(modified(path.as_ref()))),
+            current: Arc::new(Mutex::new(config)),
+            subscribers: Arc::default(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Receives every config that was successfully reloaded from now on.
+    /// Each receiver gets its own copy.
+    pub fn receiver(&self) -> Receiver<Config> {
+        let (tx, rx) = flume::unbounded();
+        self.subscribers.lock().push(tx);
+        rx
+    }
+
+    /// The running config, even if the file on disk has since been broken.
+    pub fn current(&self) -> Config {
+        self.current.lock().clone()
+    }
+
+    fn send(&self, config: Config) {
+        *self.current.lock() = config.clone();
+        self.subscribers
+            .lock()
+            .retain(|x| x.send(config.clone()).is_ok());
+    }
+
+    /// Loads and validates the config file, keeping the current config if it is invalid.
+    pub fn reload(&self) -> Result<()> {
+        match Config::load(self.path()) {
+            Ok(config) => {
+                info!(path:% = self.path.display(); "Reloaded config");
+                self.send(config);
+                Ok(())
+            }
+            Err(err) => {
+                warn!("Keeping previous config: {err:#}");
+                Err(err)
+            }
+        }
+    }
+
+    /// Applies `edit` to the config on disk, then saves it and hands it to the app if the result is valid.
+    pub fn update<T>(&self, edit: impl FnOnce(&mut Config) -> Result<T>) -> Result<T> {
+        let mut modified_lock = self.modified.lock();
+        let mut config = Config::load(self.path())?;
+        let out = edit(&mut config)?;
+        config.check(self.path())?;
+
+        config.save_channels(self.path())?;
+        *modified_lock = modified(self.path());
+        info!(path:% = self.path.display(); "Saved config");
+
+        self.send(config);
+        Ok(out)
+    }
+
+    /// Reloads whenever the config file changes or the process gets a SIGHUP.
+    pub fn watch(&self) {
+        thread::spawn(clone!([{ self.clone() } as this], move || {
+            loop {
+                thread::sleep(POLL_INTERVAL);
+                let current = modified(this.path());
+                let mut last = this.modified.lock();
+                if current != *last {
+                    *last = current;
+                    drop(last);
+                    let _ = this.reload();
+                }
+            }
+        }));
+
+        #[cfg(unix)]
+        thread::spawn(clone!([{ self.clone() } as this], move || {
+            use signal_hook::{consts::SIGHUP, iterator::Signals};
+            let mut signals = match Signals::new([SIGHUP]) {
+                Ok(signals) => signals,
+                Err(err) => {
+                    error!("Can't reload on SIGHUP: {err}");
+                    return;
+                }
+            };
+            for _ in signals.forever() {
+                let _ = this.reload();
+            }
+        }));
+    }
+}
+
+pub fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|x| x.modified()).ok()
+}
@@ -0,0 +1,111 @@
+use std::iter;
+
+use crate::{
+    config::{ChannelConfig, ScanConfig},
+    consts::IQ_CUTOFF_FREQ,
+};
+
+/// Hops the center frequency between groups of channels that each fit in the sampled bandwidth.
+pub struct Scanner {
+    groups: Vec<Group>,
+    /// Order groups are visited in, with priority groups repeated between the others.
+    schedule: Vec<usize>,
+    position: usize,
+
+    dwell: f32,
+    remaining: f32,
+}
+
+struct Group {
+    center: u32,
+    channels: Vec<usize>,
+}
+
+impl Scanner {
+    /// Scans the channels at `indices`, which are kept as the indices the scanner reports.
+    pub fn new(
+        channels: &[ChannelConfig],
+        indices: Vec<usize>,
+        sample_rate: u32,
+        config: &ScanConfig,
+    ) -> Self {
+        let groups = group_channels(channels, indices, sample_rate);
+
+        let priority = |group: &Group| group.channels.iter().any(|&x| channels[x].priority);
+        let (high, low): (Vec<_>, Vec<_>) = (0..groups.len()).partition(|&x| priority(&groups[x]));
+        let schedule = if high.is_empty() || low.is_empty() {
+            (0..groups.len()).collect()
+        } else {
+            low.iter()
+                .flat_map(|&x| iter::once(x).chain(high.iter().copied()))
+                .collect()
+        };
+
+        Self {
+            groups,
+            schedule,
+            position: 0,
+            dwell: config.dwell,
+            remaining: config.dwell,
+        }
+    }
+
+    fn group(&self) -> &Group {
+        &self.groups[self.schedule[self.position]]
+    }
+
+    pub fn center(&self) -> u32 {
+        self.group().center
+    }
+
+    /// Indices of the channels that can be received at the current center frequency.
+    pub fn channels(&self) -> &[usize] {
+        &self.group().channels
+    }
+
+    /// Advances the scanner by `elapsed` seconds, returning the new center frequency if it hopped.
+    /// While `busy` (any channel in the group is open or recording) the scanner stays on the current group.
+    pub fn update(&mut self, elapsed: f32, busy: bool) -> Option<u32> {
+        if busy {
+            self.remaining = self.dwell;
+            return None;
+        }
+
+        self.remaining -= elapsed;
+        if self.remaining > 0.0 || self.schedule.len() <= 1 {
+            return None;
+        }
+
+        self.remaining = self.dwell;
+        self.position = (self.position + 1) % self.schedule.len();
+        Some(self.center())
+    }
+}
+
+/// Packs channels into groups that span at most the usable bandwidth, tuning each to the middle of its channels.
+fn group_channels(channels: &[ChannelConfig], indices: Vec<usize>, sample_rate: u32) -> Vec<Group> {
+    let span = 2 * (sample_rate / 2).saturating_sub(IQ_CUTOFF_FREQ as u32);
+
+    let mut sorted = indices;
+    sorted.sort_by_key(|&x| channels[x].freq);
+
+    let mut groups = Vec::<(u32, Vec<usize>)>::new();
+    for idx in sorted {
+        let freq = channels[idx].freq;
+        match groups.last_mut() {
+            Some((lowest, group)) if freq - *lowest <= span => group.push(idx),
+            _ => groups.push((freq, vec![idx])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(lowest, channels_in_group)| {
+            let highest = channels[*channels_in_group.last().unwrap()].freq;
+            Group {
+                center: lowest + (highest - lowest) / 2,
+                channels: channels_in_group,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,169 @@
+/// Number of buffers to measure before deciding whether to change the gain.
+const INTERVAL: usize = 32;
+/// Step the gain down right away if more than this fraction of samples hit the ADC limits.
+const CLIP_THRESHOLD: f32 = 0.001;
+/// Step the gain up if the RMS level of the IQ samples is below this, and nothing clipped.
+const RAISE_LEVEL: f32 = 0.1;
+/// Step the gain down if the RMS level is above this. Far enough from [`RAISE_LEVEL`] that
+/// one step can't cross both.
+const LOWER_LEVEL: f32 = 0.35;
+/// Consecutive intervals that must call for the same step before it's taken, unless clipping.
+const HOLD: usize = 4;
+
+/// Software AGC that steps the tuner through its supported gains based on the raw IQ samples.
+pub struct Agc {
+    /// Supported gains in tenths of a dB, ascending.
+    gains: Vec<i32>,
+    index: usize,
+
+    buffers: usize,
+    samples: usize,
+    clipped: usize,
+    power: f32,
+    /// The step the last intervals called for, and how many in a row did.
+    pending: (Step, usize),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Step {
+    Up,
+    Down,
+    Stay,
+}
+
+impl Agc {
+    /// Starts at the supported gain closest to `initial`, which the tuner should be set to.
+    pub fn new(mut gains: Vec<i32>, initial: i32) -> Self {
+        gains.sort();
+        let index = (0..gains.len())
+            .min_by_key(|&i| (gains[i] - initial).abs())
+            .unwrap_or_default();
+
+        Self {
+            gains,
+            index,
+            buffers: 0,
+            samples: 0,
+            clipped: 0,
+            power: 0.0,
+            pending: (Step::Stay, 0),
+        }
+    }
+
+    /// The current gain in tenths of a dB.
+    pub fn gain(&self) -> i32 {
+        self.gains[self.index]
+    }
+
+    /// Measures a buffer of raw samples, returning the new gain if the tuner should be changed.
+    pub fn update(&mut self, data: &[u8]) -> Option<i32> {
+        self.buffers += 1;
+        self.samples += data.len();
+        for &sample in data {
+            self.clipped += (sample == 0 || sample == u8::MAX) as usize;
+            let value = sample as f32 / 127.5 - 1.0;
+            self.power += value * value;
+        }
+
+        if self.buffers < INTERVAL {
+            return None;
+        }
+
+        let clipped = self.clipped as f32 / self.samples as f32;
+        let rms = (self.power / self.samples as f32).sqrt();
+        (self.buffers, self.samples, self.clipped, self.power) = (0, 0, 0, 0.0);
+
+        let step = if clipped > CLIP_THRESHOLD {
+            self.pending = (Step::Down, HOLD);
+            Step::Down
+        } else {
+            let step = if rms > LOWER_LEVEL {
+                Step::Down
+            } else if rms < RAISE_LEVEL && clipped == 0.0 {
+                Step::Up
+            } else {
+                Step::Stay
+            };
+
+            let held = if self.pending.0 == step {
+                self.pending.1 + 1
+            } else {
+                1
+            };
+            self.pending = (step, held);
+            if held < HOLD {
+                return None;
+            }
+            step
+        };
+
+        let index = match step {
+            Step::Up if self.index + 1 < self.gains.len() => self.index + 1,
+            Step::Down => self.index.checked_sub(1)?,
+            _ => return None,
+        };
+
+        self.index = index;
+        self.pending = (Step::Stay, 0);
+        Some(self.gain())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Agc, HOLD, INTERVAL};
+
+    const GAINS: [i32; 4] = [0, 100, 200, 300];
+
+    /// Runs one interval of buffers with the given bytes.
+    fn interval(agc: &mut Agc, data: &[u8]) -> Option<i32> {
+        (0..INTERVAL).filter_map(|_| agc.update(data)).last()
+    }
+
+    /// IQ bytes with an RMS level of about `rms`.
+    fn level(rms: f32) -> Vec<u8> {
+        let offset = (rms * 127.5).round() as u8;
+        (0..1024)
+            .map(|i| match i % 2 {
+                0 => 128 + offset,
+                _ => 127 - offset,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn starts_at_closest_supported_gain() {
+        assert_eq!(Agc::new(GAINS.to_vec(), 140).gain(), 100);
+    }
+
+    #[test]
+    fn clipping_lowers_gain_immediately() {
+        let mut agc = Agc::new(GAINS.to_vec(), 300);
+        let clipped = (0..1024).map(|i| [0, 255][i % 2]).collect::<Vec<_>>();
+        assert_eq!(interval(&mut agc, &clipped), Some(200));
+        assert_eq!(interval(&mut agc, &clipped), Some(100));
+    }
+
+    #[test]
+    fn quiet_input_raises_gain_after_holding() {
+        let mut agc = Agc::new(GAINS.to_vec(), 0);
+        let quiet = level(0.05);
+        for _ in 1..HOLD {
+            assert_eq!(interval(&mut agc, &quiet), None);
+        }
+        assert_eq!(interval(&mut agc, &quiet), Some(100));
+    }
+
+    #[test]
+    fn steady_input_near_boundary_holds_gain() {
+        let mut agc = Agc::new(GAINS.to_vec(), 100);
+        // Alternating just either side of the raise level never holds long enough to step
+        for idx in 0..HOLD * 10 {
+            let rms = if idx % 2 == 0 { 0.09 } else { 0.11 };
+            assert_eq!(interval(&mut agc, &level(rms)), None);
+        }
+        for _ in 0..HOLD * 10 {
+            assert_eq!(interval(&mut agc, &level(0.11)), None);
+        }
+    }
+}
@@ -1,18 +1,23 @@
-use std::{num::NonZeroU32, rc::Rc};
+use std::{collections::VecDeque, num::NonZeroU32, rc::Rc};
 
 use anyhow::Result;
 use flume::Receiver;
-use num_complex::{Complex, ComplexFloat};
-use rustfft::FftPlanner;
-use softbuffer::{Buffer, Context, Surface};
+use num_complex::Complex;
+use softbuffer::{Context, Surface};
 use winit::{
     application::ApplicationHandler,
-    event::WindowEvent,
-    event_loop::{ActiveEventLoop, ControlFlow, EventLoopBuilder},
-    platform::windows::EventLoopBuilderExtWindows,
+    event::{ElementState, KeyEvent, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{Key, NamedKey},
     window::{self, WindowAttributes, WindowId},
 };
 
+use crate::{
+    consts::{IQ_CUTOFF_FREQ, SAMPLE_RATE},
+    signal::spectrum::Spectrum,
+    web::ChannelMarker,
+};
+
 type Window = Rc<window::Window>;
 
 const COLOR_SCHEME: &[Color] = &[
@@ -23,10 +28,41 @@ const COLOR_SCHEME: &[Color] = &[
     Color::hex(0xFFD76B),
     Color::hex(0xFFFFFF),
 ];
+const OPEN_COLOR: Color = Color::hex(0x00CC00);
+const CLOSED_COLOR: Color = Color::hex(0x888888);
+const TEXT_COLOR: Color = Color::hex(0xFFFFFF);
+
+const FFT_SIZE: usize = 2048;
+/// Frequency axis labels are placed every this many Hz.
+const TICK_SPACING: u32 = 25_000;
+const AXIS_HEIGHT: usize = 20;
+/// Height of the strip showing each channel's level and squelch.
+const LEVEL_HEIGHT: usize = 50;
+/// Lowest level shown in the level strip, in dB.
+const LEVEL_MIN_DB: f32 = -60.0;
+/// How far the arrow and +/- keys move the waterfall's dB range.
+const RANGE_STEP: f32 = 5.0;
+
+/// A buffer of IQ samples along with what the channels in it were doing.
+pub struct Frame {
+    pub iq: Vec<Complex<f32>>,
+    pub center_freq: u32,
+    pub channels: Vec<ChannelMarker>,
+}
 
 struct Debug {
     state: Option<State>,
-    rx: Receiver<Vec<Complex<f32>>>,
+    view: View,
+}
+
+struct View {
+    rx: Receiver<Frame>,
+    spectrum: Spectrum,
+    /// Spectrum of each frame in dB, newest first.
+    rows: VecDeque<Vec<f32>>,
+    last: Option<Frame>,
+    /// Levels in dB mapped to the bottom and top of the color scheme.
+    range: (f32, f32),
 }
 
 struct State {
@@ -52,6 +88,7 @@ impl ApplicationHandler for Debug {
             surface,
             size: (0, 0),
         });
+        self.update_title();
     }
 
     fn window_event(
@@ -70,58 +107,229 @@ impl ApplicationHandler for Debug {
                 event_loop.exit();
             }
             WindowEvent::RedrawRequested => {
+                let size = state.size;
+                if size.0 == 0 || size.1 == 0 {
+                    return;
+                }
+
+                self.view.receive(size.1 as usize);
                 let mut buffer = state.surface.buffer_mut().unwrap();
-                draw(&mut buffer, state.size, &self.rx);
+                self.view.draw(&mut Canvas {
+                    buffer: &mut buffer,
+                    width: size.0 as usize,
+                    height: size.1 as usize,
+                });
                 buffer.present().unwrap();
                 state.window.request_redraw();
             }
             WindowEvent::Resized(new_size) => {
-                state.size = (new_size.width, new_size.height);
-                state
-                    .surface
-                    .resize(
-                        NonZeroU32::new(new_size.width).unwrap(),
-                        NonZeroU32::new(new_size.height).unwrap(),
-                    )
-                    .unwrap();
+                let (Some(width), Some(height)) = (
+                    NonZeroU32::new(new_size.width),
+                    NonZeroU32::new(new_size.height),
+                ) else {
+                    return;
+                };
+                state.size = (width.get(), height.get());
+                state.surface.resize(width, height).unwrap();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key,
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                let (min, max) = &mut self.view.range;
+                match logical_key.as_ref() {
+                    Key::Named(NamedKey::ArrowUp) => {
+                        (*min, *max) = (*min + RANGE_STEP, *max + RANGE_STEP)
+                    }
+                    Key::Named(NamedKey::ArrowDown) => {
+                        (*min, *max) = (*min - RANGE_STEP, *max - RANGE_STEP)
+                    }
+                    Key::Character("+" | "=") if *max - *min > RANGE_STEP * 2.0 => {
+                        *min += RANGE_STEP
+                    }
+                    Key::Character("-") => *min -= RANGE_STEP,
+                    _ => return,
+                }
+                self.update_title();
             }
             _ => (),
         }
     }
 }
 
-fn draw(buffer: &mut Buffer<Window, Window>, size: (u32, u32), rx: &Receiver<Vec<Complex<f32>>>) {
-    while let Ok(mut row) = rx.try_recv() {
-        FftPlanner::new()
-            .plan_fft_forward(row.len())
-            .process(&mut row);
-        let row = &row[0..row.len() / 2];
-
-        let last_row = (&buffer[size.0 as usize..(size.0 * size.1) as usize]).to_owned();
-        buffer[0..(size.0 * (size.1 - 1)) as usize].copy_from_slice(&last_row);
-
-        let bins_per_pixel = row.len() as f32 / size.0 as f32;
-        for x in 0..size.0 {
-            let start = (x as f32 * bins_per_pixel) as usize;
-            let end = ((x + 1) as f32 * bins_per_pixel) as usize;
-            let val = row[start..end].iter().sum::<Complex<f32>>() / (end - start) as f32;
-            let color = color(1.0 - (-val.abs()).exp());
-            buffer[(size.0 * (size.1 - 1) + x) as usize] = color.to_u32();
+impl Debug {
+    fn update_title(&self) {
+        let Some(state) = &self.state else {
+            return;
+        };
+
+        let (min, max) = self.view.range;
+        state.window.set_title(&format!(
+            "Radio History Debug | {min} to {max} dB (up/down to move, +/- to zoom)"
+        ));
+    }
+}
+
+impl View {
+    /// Turns every waiting frame into a waterfall row, keeping at most `height` rows.
+    fn receive(&mut self, height: usize) {
+        while let Ok(frame) = self.rx.try_recv() {
+            self.rows.push_front(self.spectrum.process(&frame.iq));
+            self.last = Some(frame);
+        }
+        self.rows.truncate(height);
+    }
+
+    fn draw(&self, canvas: &mut Canvas) {
+        canvas.buffer.fill(0);
+        let Some(frame) = &self.last else {
+            return;
+        };
+
+        let width = canvas.width;
+        let x_of = |freq: f32| {
+            ((freq - frame.center_freq as f32) / SAMPLE_RATE as f32 + 0.5) * width as f32
+        };
+
+        let top = AXIS_HEIGHT + LEVEL_HEIGHT;
+        for (y, row) in (top..canvas.height).zip(&self.rows) {
+            for x in 0..width {
+                let start = x * row.len() / width;
+                let end = ((x + 1) * row.len() / width).max(start + 1);
+                let db = row[start..end].iter().copied().fold(f32::MIN, f32::max);
+                let value = (db - self.range.0) / (self.range.1 - self.range.0);
+                canvas.buffer[y * width + x] = color(value.clamp(0.0, 1.0)).to_u32();
+            }
+        }
+
+        let first_tick = (frame.center_freq - SAMPLE_RATE / 2).div_ceil(TICK_SPACING);
+        let last_tick = (frame.center_freq + SAMPLE_RATE / 2) / TICK_SPACING;
+        for tick in first_tick..=last_tick {
+            let freq = tick * TICK_SPACING;
+            let x = x_of(freq as f32) as isize;
+            canvas.rect(x, AXIS_HEIGHT as isize - 4, 1, 4, TEXT_COLOR);
+
+            let label = format!("{:.3}", freq as f64 / 1e6);
+            let label_width = label.len() as isize * 4 * FONT_SCALE;
+            canvas.text(x - label_width / 2, 2, &label, TEXT_COLOR);
+        }
+
+        let level_y = |db: f32| {
+            let value = ((db - LEVEL_MIN_DB) / -LEVEL_MIN_DB).clamp(0.0, 1.0);
+            (top as f32 - value * LEVEL_HEIGHT as f32) as isize
+        };
+        for channel in &frame.channels {
+            let color = if channel.open {
+                OPEN_COLOR
+            } else {
+                CLOSED_COLOR
+            };
+            let left = x_of(channel.freq as f32 - IQ_CUTOFF_FREQ) as isize;
+            let right = x_of(channel.freq as f32 + IQ_CUTOFF_FREQ) as isize;
+            let center = (left + right) / 2;
+
+            for x in [left, right] {
+                canvas.rect(x, AXIS_HEIGHT as isize, 1, canvas.height as isize, color);
+            }
+
+            let level = level_y(20.0 * channel.rms.log10());
+            canvas.rect(center - 3, level, 7, top as isize - level, color);
+
+            let squelch = level_y(20.0 * channel.squelch.log10());
+            canvas.rect(left, squelch, right - left, 1, TEXT_COLOR);
         }
     }
 }
 
-pub fn start(rx: Receiver<Vec<Complex<f32>>>) -> Result<()> {
-    let event_loop = EventLoopBuilder::default().with_any_thread(true).build()?;
+/// Runs the debug window on the current thread until it's closed.
+/// This has to be the main thread, as some platforms can't create windows on any other.
+pub fn start(rx: Receiver<Frame>) -> Result<()> {
+    let event_loop = EventLoop::new()?;
     event_loop.set_control_flow(ControlFlow::Wait);
-    event_loop.run_app(&mut Debug { state: None, rx })?;
+    event_loop.run_app(&mut Debug {
+        state: None,
+        view: View {
+            rx,
+            spectrum: Spectrum::new(FFT_SIZE),
+            rows: VecDeque::new(),
+            last: None,
+            range: (-100.0, -20.0),
+        },
+    })?;
     Ok(())
 }
 
+struct Canvas<'a> {
+    buffer: &'a mut [u32],
+    width: usize,
+    height: usize,
+}
+
+impl Canvas<'_> {
+    /// Fills a rectangle, clipped to the canvas.
+    fn rect(&mut self, x: isize, y: isize, width: isize, height: isize, color: Color) {
+        let clip = |start: isize, size: isize, max: usize| {
+            start.clamp(0, max as isize) as usize..(start + size).clamp(0, max as isize) as usize
+        };
+
+        for y in clip(y, height, self.height) {
+            for x in clip(x, width, self.width) {
+                self.buffer[y * self.width + x] = color.to_u32();
+            }
+        }
+    }
+
+    /// Draws digits, dots and dashes with the built in pixel font.
+    fn text(&mut self, x: isize, y: isize, text: &str, color: Color) {
+        for (i, char) in text.chars().enumerate() {
+            let Some(glyph) = glyph(char) else {
+                continue;
+            };
+
+            let char_x = x + i as isize * 4 * FONT_SCALE;
+            for (row, bits) in glyph.iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) != 0 {
+                        let (px, py) =
+                            (char_x + column * FONT_SCALE, y + row as isize * FONT_SCALE);
+                        self.rect(px, py, FONT_SCALE, FONT_SCALE, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+const FONT_SCALE: isize = 2;
+
+/// 3x5 pixel glyphs, one row of bits per line.
+fn glyph(char: char) -> Option<[u8; 5]> {
+    Some(match char {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        _ => return None,
+    })
+}
+
 fn color(val: f32) -> Color {
     debug_assert!((0. ..=1.).contains(&val));
-    let sections = COLOR_SCHEME.len() - 2;
-    let section = (sections as f32 * val).floor() as usize;
+    let sections = COLOR_SCHEME.len() - 1;
+    let section = ((sections as f32 * val).floor() as usize).min(sections - 1);
 
     COLOR_SCHEME[section].lerp(
         &COLOR_SCHEME[section + 1],
@@ -149,7 +357,7 @@ impl Color {
         )
     }
 
-    const fn to_u32(&self) -> u32 {
+    const fn to_u32(self) -> u32 {
         (self.r as u32) << 16 | (self.g as u32) << 8 | self.b as u32
     }
 
@@ -2,24 +2,37 @@ use std::{f32::consts::PI, iter};
 
 use itertools::Itertools;
 use num_complex::Complex;
-use num_traits::Zero;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     consts::{AUDIO_CUTOFF_FREQ, IQ_CUTOFF_FREQ, SAMPLE_RATE, WAVE_SAMPLE_RATE},
     filters::{down_sample::DownSampleExt, low_pass::LowPassExt, offset::OffsetExt},
 };
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Narrowband FM, used by most land mobile and marine radio.
+    #[default]
+    Fm,
+    /// Amplitude modulation, used by airband.
+    Am,
+}
+
 pub struct Demodulator {
     iq: Vec<Complex<f32>>,
+}
+
+/// What a channel's demodulation carries over from one buffer to the next.
+#[derive(Default)]
+pub struct ChannelState {
+    /// Last filtered sample, so the first sample of the next buffer has something to compare to.
     last_sample: Complex<f32>,
 }
 
 impl Demodulator {
     pub fn empty() -> Self {
-        Self {
-            iq: Vec::new(),
-            last_sample: Complex::zero(),
-        }
+        Self { iq: Vec::new() }
     }
 
     pub fn replace(&mut self, data: &[u8]) {
@@ -29,40 +42,50 @@ impl Demodulator {
             .collect::<Vec<_>>();
     }
 
-    pub fn rms(&self, offset: u32) -> f32 {
+    /// RMS level of the signal within the channel `offset` Hz from the center frequency.
+    pub fn rms(&self, offset: i32) -> f32 {
         (self
             .iq
             .iter()
             .copied()
             .offset(offset as f32, SAMPLE_RATE)
+            .low_pass(SAMPLE_RATE, IQ_CUTOFF_FREQ)
             .map(|c| c.re * c.re + c.im * c.im)
             .sum::<f32>()
-            / self.iq.len() as f32)
+            / self.iq.len().max(1) as f32)
             .sqrt()
     }
 
-    #[cfg(feature = "debug")]
     pub fn iq(&self) -> &[Complex<f32>] {
         &self.iq
     }
 
-    pub fn audio(&mut self, offset: u32, gain: f32) -> Vec<f32> {
-        let mut audio = iter::once(self.last_sample)
-            .chain(self.iq.iter().copied())
+    pub fn audio(&self, state: &mut ChannelState, offset: i32, gain: f32, mode: Mode) -> Vec<f32> {
+        let filtered = self
+            .iq
+            .iter()
+            .copied()
             .offset(offset as f32, SAMPLE_RATE)
-            .low_pass(SAMPLE_RATE, IQ_CUTOFF_FREQ)
+            .low_pass(SAMPLE_RATE, IQ_CUTOFF_FREQ);
+        let mut audio = iter::once(state.last_sample)
+            .chain(filtered)
             .tuple_windows()
             .map(|(a, b)| {
-                self.last_sample = b;
+                state.last_sample = b;
+                match mode {
+                    Mode::Fm => {
+                        let mut angle = b.arg() - a.arg();
+                        if angle > PI {
+                            angle -= 2.0 * PI;
+                        } else if angle < -PI {
+                            angle += 2.0 * PI;
+                        }
 
-                let mut angle = b.arg() - a.arg();
-                if angle > PI {
-                    angle -= 2.0 * PI;
-                } else if angle < -PI {
-                    angle += 2.0 * PI;
+                        angle * gain
+                    }
+                    // The carrier's DC offset is removed along with the mean below
+                    Mode::Am => b.norm() * gain,
                 }
-
-                angle * gain
             })
             .low_pass(SAMPLE_RATE, AUDIO_CUTOFF_FREQ)
             .down_sample(SAMPLE_RATE, WAVE_SAMPLE_RATE)
@@ -74,3 +97,124 @@ impl Demodulator {
         audio
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use super::{ChannelState, Demodulator, Mode};
+    use crate::consts::{SAMPLE_RATE, WAVE_SAMPLE_RATE};
+
+    /// Raw IQ bytes of carriers at `(offset, tone)` Hz, each frequency modulated by a sine at its tone.
+    fn fm_signal(carriers: &[(f32, f32)]) -> Vec<u8> {
+        let amplitude = 100.0 / carriers.len() as f32;
+        (0..16_384 / 2)
+            .flat_map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let (re, im) = carriers
+                    .iter()
+                    .fold((0.0, 0.0), |(re, im), (offset, tone)| {
+                        let phase = TAU * offset * t + 5_000.0 / tone * (TAU * tone * t).sin();
+                        (re + phase.cos(), im + phase.sin())
+                    });
+                [re, im].map(|x| (127.5 + x * amplitude) as u8)
+            })
+            .collect()
+    }
+
+    fn tone_power(audio: &[f32], freq: f32) -> f32 {
+        let (re, im) = audio
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(re, im), (i, x)| {
+                let angle = TAU * freq * i as f32 / WAVE_SAMPLE_RATE as f32;
+                (re + x * angle.cos(), im + x * angle.sin())
+            });
+        (re * re + im * im) / audio.len() as f32
+    }
+
+    #[test]
+    fn empty_buffer() {
+        let mut demodulator = Demodulator::empty();
+        demodulator.replace(&[0]);
+        assert_eq!(demodulator.rms(0), 0.0);
+        let audio = demodulator.audio(&mut ChannelState::default(), 0, 1.0, Mode::Fm);
+        assert!(audio.is_empty());
+    }
+
+    #[test]
+    fn rms_only_measures_its_channel() {
+        let mut demodulator = Demodulator::empty();
+        demodulator.replace(&fm_signal(&[(-60_000.0, 1_000.0)]));
+
+        let inside = demodulator.rms(-60_000);
+        let mirror = demodulator.rms(60_000);
+        assert!(inside > mirror * 4.0, "{inside} vs {mirror}");
+    }
+
+    #[test]
+    fn demodulates_negative_offset() {
+        let mut demodulator = Demodulator::empty();
+        demodulator.replace(&fm_signal(&[(-75_000.0, 1_000.0)]));
+
+        let audio = demodulator.audio(&mut ChannelState::default(), -75_000, 1.0, Mode::Fm);
+        let signal = tone_power(&audio, 1_000.0);
+        let other = tone_power(&audio, 2_500.0);
+        assert!(signal > other * 100.0, "{signal} vs {other}");
+    }
+
+    #[test]
+    fn negative_and_positive_offsets_are_separate() {
+        let mut demodulator = Demodulator::empty();
+        demodulator.replace(&fm_signal(&[(-50_000.0, 1_000.0), (50_000.0, 1_700.0)]));
+
+        let below = demodulator.audio(&mut ChannelState::default(), -50_000, 1.0, Mode::Fm);
+        assert!(tone_power(&below, 1_000.0) > tone_power(&below, 1_700.0) * 10.0);
+
+        let above = demodulator.audio(&mut ChannelState::default(), 50_000, 1.0, Mode::Fm);
+        assert!(tone_power(&above, 1_700.0) > tone_power(&above, 1_000.0) * 10.0);
+    }
+
+    #[test]
+    fn channels_keep_their_own_state() {
+        let first = fm_signal(&[(-50_000.0, 1_000.0), (50_000.0, 1_700.0)]);
+        let second = fm_signal(&[(-50_000.0, 1_300.0), (50_000.0, 2_100.0)]);
+        let mut demodulator = Demodulator::empty();
+
+        let mut alone = ChannelState::default();
+        demodulator.replace(&first);
+        demodulator.audio(&mut alone, -50_000, 1.0, Mode::Fm);
+        demodulator.replace(&second);
+        let expected = demodulator.audio(&mut alone, -50_000, 1.0, Mode::Fm);
+
+        let (mut below, mut above) = (ChannelState::default(), ChannelState::default());
+        demodulator.replace(&first);
+        demodulator.audio(&mut below, -50_000, 1.0, Mode::Fm);
+        demodulator.audio(&mut above, 50_000, 1.0, Mode::Fm);
+        demodulator.replace(&second);
+        assert_eq!(
+            demodulator.audio(&mut below, -50_000, 1.0, Mode::Fm),
+            expected
+        );
+    }
+
+    #[test]
+    fn demodulates_am() {
+        let data = (0..16_384 / 2)
+            .flat_map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let envelope = 50.0 * (1.0 + 0.5 * (TAU * 1_000.0 * t).sin());
+                let phase = TAU * 40_000.0 * t;
+                [phase.cos(), phase.sin()].map(|x| (127.5 + x * envelope) as u8)
+            })
+            .collect::<Vec<_>>();
+
+        let mut demodulator = Demodulator::empty();
+        demodulator.replace(&data);
+
+        let audio = demodulator.audio(&mut ChannelState::default(), 40_000, 1.0, Mode::Am);
+        let signal = tone_power(&audio, 1_000.0);
+        let other = tone_power(&audio, 2_500.0);
+        assert!(signal > other * 100.0, "{signal} vs {other}");
+    }
+}
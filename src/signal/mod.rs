@@ -1,4 +1,7 @@
+pub mod agc;
 #[cfg(feature = "debug")]
 pub mod debug;
 pub mod demodulate;
+pub mod spectrum;
+pub mod tone;
 pub mod transcribe;
@@ -0,0 +1,83 @@
+use std::{f32::consts::TAU, sync::Arc};
+
+use num_complex::Complex;
+use num_traits::Zero;
+use rustfft::{Fft, FftPlanner};
+
+/// Power spectrum of IQ samples, keeping the FFT plan and window around between calls.
+pub struct Spectrum {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    buffer: Vec<Complex<f32>>,
+}
+
+impl Spectrum {
+    pub fn new(size: usize) -> Self {
+        let window = (0..size)
+            .map(|i| 0.5 - 0.5 * (TAU * i as f32 / size as f32).cos())
+            .collect();
+
+        Self {
+            fft: FftPlanner::new().plan_fft_forward(size),
+            window,
+            buffer: vec![Complex::zero(); size],
+        }
+    }
+
+    /// Power of each bin in dB relative to a full scale tone, averaged over every full window in `iq`.
+    /// Bins are ordered from the lowest to highest frequency, so the center frequency is in the middle.
+    pub fn process(&mut self, iq: &[Complex<f32>]) -> Vec<f32> {
+        let size = self.window.len();
+        let mut power = vec![0.0; size];
+
+        let windows = iq.chunks_exact(size);
+        let count = windows.len().max(1);
+        for samples in windows {
+            for (out, (sample, weight)) in
+                self.buffer.iter_mut().zip(samples.iter().zip(&self.window))
+            {
+                *out = sample * weight;
+            }
+
+            self.fft.process(&mut self.buffer);
+            for (power, bin) in power.iter_mut().zip(&self.buffer) {
+                *power += bin.norm_sqr();
+            }
+        }
+
+        // FFT bins start at 0 Hz and wrap around to the negative frequencies halfway through
+        power.rotate_left(size / 2);
+
+        let gain = self.window.iter().sum::<f32>().powi(2) * count as f32;
+        power
+            .into_iter()
+            .map(|x| 10.0 * (x / gain).max(1e-12).log10())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use num_complex::Complex;
+
+    use super::Spectrum;
+
+    #[test]
+    fn tone_lands_in_its_bin() {
+        // 1024 bins over 250 kHz, so a -62.5 kHz tone is 256 bins below the center
+        let iq = (0..8192)
+            .map(|i| Complex::from_polar(0.5, TAU * -62_500.0 * i as f32 / 250_000.0))
+            .collect::<Vec<_>>();
+
+        let bins = Spectrum::new(1024).process(&iq);
+        let peak = (0..bins.len())
+            .max_by(|&a, &b| bins[a].total_cmp(&bins[b]))
+            .unwrap();
+
+        assert_eq!(peak, 512 - 256);
+        assert!((bins[peak] - -6.0).abs() < 0.5, "{}", bins[peak]);
+        assert!(bins[100] < -60.0, "{}", bins[100]);
+    }
+}
@@ -0,0 +1,341 @@
+use std::{collections::HashMap, f32::consts::TAU, fmt::Display, sync::OnceLock};
+
+use num_complex::Complex;
+use serde::{Deserialize, Serialize};
+
+use crate::{consts::WAVE_SAMPLE_RATE, filters::low_pass::LowPassFilter, misc::serialize_f32};
+
+/// Standard CTCSS tones in Hz.
+const CTCSS_TONES: &[f32] = &[
+    67.0, 69.3, 71.9, 74.4, 77.0, 79.7, 82.5, 85.4, 88.5, 91.5, 94.8, 97.4, 100.0, 103.5, 107.2,
+    110.9, 114.8, 118.8, 123.0, 127.3, 131.8, 136.5, 141.3, 146.2, 151.4, 156.7, 159.8, 162.2,
+    165.5, 167.9, 171.3, 173.8, 177.3, 179.9, 183.5, 186.2, 189.9, 192.8, 196.6, 199.5, 203.5,
+    206.5, 210.7, 218.1, 225.7, 229.1, 233.6, 241.8, 250.3, 254.1,
+];
+
+/// Standard DCS codes, written as their octal digits.
+const DCS_CODES: &[u16] = &[
+    23, 25, 26, 31, 32, 36, 43, 47, 51, 53, 54, 65, 71, 72, 73, 74, 114, 115, 116, 122, 125, 131,
+    132, 134, 143, 145, 152, 155, 156, 162, 165, 172, 174, 205, 212, 223, 225, 226, 243, 244, 245,
+    246, 251, 252, 255, 261, 263, 265, 266, 271, 274, 306, 311, 315, 325, 331, 332, 343, 346, 351,
+    356, 364, 365, 371, 411, 412, 413, 423, 431, 432, 445, 446, 452, 454, 455, 462, 464, 465, 466,
+    503, 506, 516, 523, 526, 532, 546, 565, 606, 612, 624, 627, 631, 632, 654, 662, 664, 703, 712,
+    723, 731, 732, 734, 743, 754,
+];
+
+/// Length of audio each CTCSS measurement is made over, giving ~2Hz of resolution.
+const CTCSS_WINDOW: usize = WAVE_SAMPLE_RATE as usize / 2;
+/// How much audio to advance between CTCSS measurements.
+const CTCSS_HOP: usize = CTCSS_WINDOW / 2;
+/// How many times stronger than the average tone bin the strongest must be to count.
+const CTCSS_THRESHOLD: f32 = 10.0;
+
+const DCS_BAUD: f32 = 134.4;
+const DCS_CUTOFF_FREQ: f32 = 300.0;
+/// Generator polynomial of the (23, 12) Golay code used by DCS.
+const GOLAY_POLY: u32 = 0xC75;
+const DCS_MASK: u32 = (1 << 23) - 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tone {
+    /// Continuous sub-audible tone, in Hz.
+    Ctcss(#[serde(serialize_with = "serialize_f32")] f32),
+    /// Digital code, written as its octal digits (e.g. `23` for D023).
+    Dcs(u16),
+}
+
+/// Detects CTCSS tones and DCS codes in demodulated audio.
+/// Audio must be passed in contiguous chunks at [`WAVE_SAMPLE_RATE`].
+pub struct ToneDetector {
+    ctcss: Vec<f32>,
+    ctcss_tone: Option<f32>,
+
+    dcs_filter: [LowPassFilter; 2],
+    dcs_phase: f32,
+    dcs_last: bool,
+    dcs_word: u32,
+    dcs_matches: (u16, u32),
+    dcs_code: Option<u16>,
+}
+
+impl ToneDetector {
+    pub fn new() -> Self {
+        Self {
+            ctcss: Vec::with_capacity(CTCSS_WINDOW),
+            ctcss_tone: None,
+
+            dcs_filter: [
+                LowPassFilter::new(WAVE_SAMPLE_RATE, DCS_CUTOFF_FREQ),
+                LowPassFilter::new(WAVE_SAMPLE_RATE, DCS_CUTOFF_FREQ),
+            ],
+            dcs_phase: 0.0,
+            dcs_last: false,
+            dcs_word: 0,
+            dcs_matches: (0, 0),
+            dcs_code: None,
+        }
+    }
+
+    /// Forget everything heard so far, used when a channel goes quiet.
+    pub fn reset(&mut self) {
+        self.ctcss.clear();
+        self.ctcss_tone = None;
+        self.dcs_matches = (0, 0);
+        self.dcs_code = None;
+    }
+
+    /// The tone or code most recently detected, with DCS taking priority.
+    pub fn tone(&self) -> Option<Tone> {
+        self.dcs_code
+            .map(Tone::Dcs)
+            .or(self.ctcss_tone.map(Tone::Ctcss))
+    }
+
+    pub fn process(&mut self, audio: &[f32]) {
+        for &sample in audio {
+            self.ctcss.push(sample);
+            if self.ctcss.len() >= CTCSS_WINDOW {
+                self.ctcss_tone = detect_ctcss(&self.ctcss);
+                self.ctcss.drain(..CTCSS_HOP);
+            }
+
+            self.process_dcs(sample);
+        }
+    }
+
+    fn process_dcs(&mut self, sample: f32) {
+        let samples_per_bit = WAVE_SAMPLE_RATE as f32 / DCS_BAUD;
+        let filtered = self
+            .dcs_filter
+            .iter_mut()
+            .fold(Complex::new(sample, 0.0), |value, filter| {
+                filter.filter(value)
+            });
+
+        // Re-align the bit clock so we sample half a bit after every transition.
+        let bit = filtered.re > 0.0;
+        if bit != self.dcs_last {
+            self.dcs_phase = samples_per_bit / 2.0;
+            self.dcs_last = bit;
+        }
+
+        self.dcs_phase += 1.0;
+        if self.dcs_phase < samples_per_bit {
+            return;
+        }
+        self.dcs_phase -= samples_per_bit;
+
+        // Codewords are sent least significant bit first
+        self.dcs_word = (self.dcs_word >> 1) | ((bit as u32) << 22);
+        match dcs_table().get(&self.dcs_word) {
+            Some(&code) if code == self.dcs_matches.0 => self.dcs_matches.1 += 1,
+            Some(&code) => self.dcs_matches = (code, 1),
+            None => self.dcs_matches = (0, 0),
+        }
+
+        // A code is only accepted after a full word of consecutive matching bits.
+        if self.dcs_matches.1 >= 23 {
+            self.dcs_code = Some(self.dcs_matches.0);
+        }
+    }
+}
+
+impl Tone {
+    /// If this is one of the standard tones or codes the detector can recognize.
+    pub fn is_standard(&self) -> bool {
+        match self {
+            Tone::Ctcss(_) => CTCSS_TONES.iter().any(|&x| self.matches(&Tone::Ctcss(x))),
+            Tone::Dcs(code) => DCS_CODES.contains(code),
+        }
+    }
+
+    /// If the detected tone `other` satisfies this one.
+    pub fn matches(&self, other: &Tone) -> bool {
+        match (self, other) {
+            (Tone::Ctcss(a), Tone::Ctcss(b)) => (a - b).abs() < 0.5,
+            (Tone::Dcs(a), Tone::Dcs(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Display for Tone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tone::Ctcss(freq) => write!(f, "CTCSS {freq:.1} Hz"),
+            Tone::Dcs(code) => write!(f, "DCS {code:03}"),
+        }
+    }
+}
+
+/// Finds the standard CTCSS tone that stands out in the window, if any.
+fn detect_ctcss(window: &[f32]) -> Option<f32> {
+    let n = window.len() as f32;
+    let windowed = window
+        .iter()
+        .enumerate()
+        .map(|(i, x)| x * (0.5 - 0.5 * (TAU * i as f32 / n).cos()))
+        .collect::<Vec<_>>();
+
+    let powers = CTCSS_TONES
+        .iter()
+        .map(|&freq| goertzel(&windowed, freq, WAVE_SAMPLE_RATE))
+        .collect::<Vec<_>>();
+    let average = powers.iter().sum::<f32>() / powers.len() as f32;
+
+    let (idx, &best) = powers
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))?;
+    (best > average * CTCSS_THRESHOLD).then_some(CTCSS_TONES[idx])
+}
+
+/// Power of a single frequency in the samples.
+fn goertzel(samples: &[f32], freq: f32, sample_rate: u32) -> f32 {
+    let coeff = 2.0 * (TAU * freq / sample_rate as f32).cos();
+    let (mut s1, mut s2) = (0.0, 0.0);
+    for &sample in samples {
+        let s = sample + coeff * s1 - s2;
+        s2 = s1;
+        s1 = s;
+    }
+
+    s1 * s1 + s2 * s2 - coeff * s1 * s2
+}
+
+/// Maps every 23 bit window of every standard code's repeating codeword (in both polarities) to the code.
+fn dcs_table() -> &'static HashMap<u32, u16> {
+    static TABLE: OnceLock<HashMap<u32, u16>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        // Inverted codewords alias other codes (D023I is D047N), so normal polarity is added first to win.
+        let mut table = HashMap::new();
+        for invert in [0, DCS_MASK] {
+            for &code in DCS_CODES {
+                let word = dcs_codeword(code);
+                for rotation in 0..23 {
+                    let rotated = ((word << rotation) | (word >> (23 - rotation))) & DCS_MASK;
+                    table.entry(rotated ^ invert).or_insert(code);
+                }
+            }
+        }
+        table
+    })
+}
+
+/// Builds the 23 bit codeword for a DCS code: nine code bits, the fixed `100` and eleven Golay parity bits.
+fn dcs_codeword(code: u16) -> u32 {
+    let octal = code
+        .to_string()
+        .bytes()
+        .fold(0, |acc, digit| (acc << 3) | (digit - b'0') as u32);
+    let data = 0x800 | (octal & 0x1FF);
+
+    let mut parity = data << 11;
+    for bit in (11..23).rev() {
+        if parity & (1 << bit) != 0 {
+            parity ^= GOLAY_POLY << (bit - 11);
+        }
+    }
+
+    data | (parity << 12)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use super::{dcs_codeword, Tone, ToneDetector, DCS_BAUD};
+    use crate::consts::WAVE_SAMPLE_RATE;
+
+    /// Deterministic noise spread over the voice band, with a couple of strong voice-like tones.
+    fn voice(samples: usize) -> Vec<f32> {
+        let mut state = 0x1234_5678_u32;
+        let mut last = 0.0;
+        (0..samples)
+            .map(|i| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let white = state as f32 / u32::MAX as f32 - 0.5;
+                // Differencing rolls off everything below the voice band
+                let noise = white - last;
+                last = white;
+
+                let t = i as f32 / WAVE_SAMPLE_RATE as f32;
+                0.3 * noise + 0.2 * (TAU * 800.0 * t).sin() + 0.1 * (TAU * 1_250.0 * t).sin()
+            })
+            .collect()
+    }
+
+    fn ctcss(freq: f32) -> Option<Tone> {
+        let samples = WAVE_SAMPLE_RATE as usize / 2;
+        let audio = voice(samples)
+            .into_iter()
+            .enumerate()
+            .map(|(i, x)| x + 0.1 * (TAU * freq * i as f32 / WAVE_SAMPLE_RATE as f32).sin())
+            .collect::<Vec<_>>();
+
+        let mut detector = ToneDetector::new();
+        detector.process(&audio);
+        detector.tone()
+    }
+
+    /// One second of a DCS code's codeword repeating, sent least significant bit first.
+    fn dcs(code: u16, inverted: bool) -> Vec<f32> {
+        let word = dcs_codeword(code);
+        let samples_per_bit = WAVE_SAMPLE_RATE as f32 / DCS_BAUD;
+        (0..WAVE_SAMPLE_RATE)
+            .map(|i| {
+                let bit = (i as f32 / samples_per_bit) as u32 % 23;
+                let high = (word >> bit) & 1 == 1;
+                if high != inverted {
+                    0.2
+                } else {
+                    -0.2
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_ctcss_under_voice() {
+        assert_eq!(ctcss(100.0), Some(Tone::Ctcss(100.0)));
+    }
+
+    #[test]
+    fn neighbouring_ctcss_tone_does_not_match() {
+        let detected = ctcss(103.5).unwrap();
+        assert_eq!(detected, Tone::Ctcss(103.5));
+        assert!(!Tone::Ctcss(100.0).matches(&detected));
+    }
+
+    #[test]
+    fn dcs_codewords() {
+        assert_eq!(dcs_codeword(23), 0x76_3813);
+        assert_eq!(dcs_codeword(754), 0x20_F9EC);
+    }
+
+    #[test]
+    fn decodes_dcs() {
+        let mut detector = ToneDetector::new();
+        detector.process(&dcs(754, false));
+        assert_eq!(detector.tone(), Some(Tone::Dcs(754)));
+    }
+
+    #[test]
+    fn decodes_inverted_dcs() {
+        // D023 inverted is the same bitstream as D047
+        let mut detector = ToneDetector::new();
+        detector.process(&dcs(23, true));
+        assert_eq!(detector.tone(), Some(Tone::Dcs(47)));
+    }
+
+    #[test]
+    fn standard_tones() {
+        assert!(Tone::Ctcss(100.0).is_standard());
+        assert!(Tone::Ctcss(100.2).is_standard());
+        assert!(!Tone::Ctcss(101.0).is_standard());
+        assert!(Tone::Dcs(23).is_standard());
+        assert!(!Tone::Dcs(24).is_standard());
+        assert!(!Tone::Dcs(23).matches(&Tone::Ctcss(100.0)));
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use whisper_rs::{self, FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
 
 pub const TRANSCRIBE_SAMPLE_RATE: u32 = 16_000;
@@ -11,7 +11,7 @@ impl Transcriber {
     pub fn new(model: &str) -> Result<Transcriber> {
         let model =
             WhisperContext::new_with_params(model, WhisperContextParameters { use_gpu: true })
-                .unwrap();
+                .with_context(|| format!("loading transcription model `{model}`"))?;
         Ok(Self { model })
     }
 
@@ -0,0 +1,150 @@
+use std::{fs, io::ErrorKind, path::PathBuf, thread, time::Duration};
+
+use anyhow::Result;
+use flume::Sender;
+use log::{error, warn};
+use serde_json::json;
+use ureq::Agent;
+use uuid::Uuid;
+
+use crate::{
+    config::SyncConfig,
+    web::database::{Database, Message},
+};
+
+/// Pushes completed messages to the central server in the background.
+/// The queue lives in the database, so messages recorded while it's unreachable are sent later,
+/// even across restarts.
+#[derive(Clone)]
+pub struct Upstream {
+    database: Database,
+    wake: Sender<()>,
+}
+
+struct Worker {
+    config: SyncConfig,
+    database: Database,
+    data_dir: PathBuf,
+    agent: Agent,
+}
+
+impl Upstream {
+    pub fn start(config: SyncConfig, database: Database, data_dir: PathBuf) -> Self {
+        let (wake, rx) = flume::unbounded();
+        let worker = Worker {
+            config,
+            database: database.clone(),
+            data_dir,
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(60))
+                .build(),
+        };
+
+        thread::spawn(move || loop {
+            if worker.send_queued() {
+                if rx.recv().is_err() {
+                    break;
+                }
+            } else {
+                // Messages that came in meanwhile will be picked up by the retry
+                thread::sleep(Duration::from_secs_f32(worker.config.retry));
+                rx.drain();
+            }
+        });
+
+        Self { database, wake }
+    }
+
+    pub fn push(&self, audio: Uuid) -> Result<()> {
+        self.database.lock().queue_sync(audio)?;
+        let _ = self.wake.send(());
+        Ok(())
+    }
+}
+
+impl Worker {
+    /// Sends everything in the queue, stopping at the first failure to try again later.
+    /// Returns if the queue was emptied.
+    fn send_queued(&self) -> bool {
+        loop {
+            let message = match self.database.lock().next_sync() {
+                Ok(Some(message)) => message,
+                Ok(None) => return true,
+                Err(err) => {
+                    error!("Failed to read the sync queue: {err:#}");
+                    return false;
+                }
+            };
+
+            if let Err(err) = self.send(&message) {
+                if !rejected(&err) {
+                    let reason = match err.downcast_ref() {
+                        Some(ureq::Error::Status(400..500, _)) => "upstream may be misconfigured",
+                        _ => "will retry",
+                    };
+                    warn!(
+                        audio:% = message.audio, retry = self.config.retry;
+                        "Failed to sync message, {reason}: {err:#}"
+                    );
+                    return false;
+                }
+
+                // It won't be accepted no matter how often it's sent, so don't hold up the rest
+                error!(audio:% = message.audio; "Upstream rejected message: {err:#}");
+            }
+
+            if let Err(err) = self.database.lock().remove_sync(message.audio) {
+                error!("Failed to update the sync queue: {err:#}");
+                return false;
+            }
+        }
+    }
+
+    /// Uploads the audio first, so the message is never on the central server without it.
+    fn send(&self, message: &Message) -> Result<()> {
+        let upstream = self.config.upstream.trim_end_matches('/');
+        let auth = format!("Bearer {}", self.config.token);
+
+        let path = self
+            .data_dir
+            .join("audio")
+            .join(format!("{}.wav", message.audio));
+        match fs::read(path) {
+            Ok(audio) => {
+                let sent = self
+                    .agent
+                    .put(&format!("{upstream}/ingest/audio/{}", message.audio))
+                    .set("Authorization", &auth)
+                    .set("Content-Type", "audio/wav")
+                    .send_bytes(&audio);
+                match sent {
+                    // Already uploaded by an earlier attempt that failed to send the message
+                    Ok(_) | Err(ureq::Error::Status(409, _)) => {}
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        let message = Message {
+            station: Some(self.config.station.clone()),
+            ..message.clone()
+        };
+        self.agent
+            .post(&format!("{upstream}/ingest/message"))
+            .set("Authorization", &auth)
+            .set("Content-Type", "application/json")
+            .send_string(&json!(message).to_string())?;
+        Ok(())
+    }
+}
+
+/// If the server refused the message itself, rather than being unreachable or set up wrong.
+/// Anything else, like a 404 from a server without `server.ingest`, is retried.
+fn rejected(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<ureq::Error>(),
+        Some(ureq::Error::Status(400 | 409 | 413 | 422, _))
+    )
+}
@@ -0,0 +1,165 @@
+use std::{
+    sync::{atomic::Ordering, Arc},
+    thread,
+    time::Instant,
+};
+
+use anyhow::Result;
+use flume::Sender;
+use log::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    alerts::Alerts,
+    metrics::Metrics,
+    misc::date_time,
+    signal::transcribe::Transcriber,
+    sync::Upstream,
+    web::{
+        database::{self, Database},
+        UiMessage,
+    },
+};
+
+/// Transcribes finished recordings on their own thread, then saves and announces them.
+/// Radios only queue their recordings, so one never waits on another's transcription.
+#[derive(Clone)]
+pub struct Transcriptions {
+    jobs: Sender<Job>,
+    metrics: Arc<Metrics>,
+}
+
+/// A recording whose audio has been saved, waiting for its transcript.
+pub struct Job {
+    pub idx: u32,
+    pub channel: String,
+    pub uuid: Uuid,
+    pub previous: Option<Uuid>,
+    pub tone: Option<String>,
+    /// Audio at [`crate::signal::transcribe::TRANSCRIBE_SAMPLE_RATE`].
+    pub buffer: Vec<f32>,
+    /// The alerts of the radio that recorded it, as they were when it finished.
+    pub alerts: Arc<Alerts>,
+}
+
+struct Worker {
+    transcriber: Transcriber,
+    database: Database,
+    upstream: Option<Upstream>,
+    metrics: Arc<Metrics>,
+    web_tx: Sender<UiMessage>,
+}
+
+impl Transcriptions {
+    pub fn start(
+        transcriber: Transcriber,
+        database: Database,
+        upstream: Option<Upstream>,
+        metrics: Arc<Metrics>,
+        web_tx: Sender<UiMessage>,
+    ) -> Self {
+        let (jobs, rx) = flume::unbounded::<Job>();
+        let mut worker = Worker {
+            transcriber,
+            database,
+            upstream,
+            metrics: metrics.clone(),
+            web_tx,
+        };
+
+        thread::spawn(move || {
+            for job in rx.iter() {
+                let (channel, uuid) = (job.channel.clone(), job.uuid);
+                if let Err(err) = worker.process(job) {
+                    let channel = channel.as_str();
+                    error!(channel, audio:% = uuid; "Failed to save recording: {err:#}");
+                }
+            }
+        });
+
+        Self { jobs, metrics }
+    }
+
+    pub fn push(&self, job: Job) {
+        self.metrics
+            .transcriptions_pending
+            .fetch_add(1, Ordering::Relaxed);
+        let _ = self.jobs.send(job);
+    }
+}
+
+impl Worker {
+    fn process(&mut self, job: Job) -> Result<()> {
+        let Job {
+            idx,
+            channel,
+            uuid,
+            previous,
+            tone,
+            buffer,
+            alerts,
+        } = job;
+
+        let start = Instant::now();
+        // The audio is still worth keeping without a transcript
+        let text = match buffer.is_empty() {
+            true => None,
+            false => self
+                .transcriber
+                .transcribe(&buffer)
+                .inspect_err(|err| warn!(audio:% = uuid; "Transcription failed: {err:#}"))
+                .ok(),
+        };
+        let text_ref = text.as_deref();
+        let elapsed = start.elapsed();
+        self.metrics
+            .transcriptions_pending
+            .fetch_sub(1, Ordering::Relaxed);
+        self.metrics.transcription.observe(elapsed.as_secs_f64());
+
+        info!(
+            channel = channel.as_str(), audio:% = uuid, elapsed:? = elapsed;
+            "{}", text_ref.unwrap_or("<No Text>")
+        );
+        self.database
+            .lock()
+            .insert_message(text_ref, uuid, previous, tone.as_deref(), &channel)?;
+        self.metrics.recorded(&channel);
+        if let Some(upstream) = &self.upstream {
+            upstream.push(uuid)?;
+        }
+
+        let date = date_time();
+        let events = text_ref
+            .map(|text| alerts.check(&channel, text, uuid, date))
+            .unwrap_or_default();
+
+        self.web_tx.send(UiMessage::Complete {
+            idx,
+            name: channel.clone(),
+            uuid,
+            message: database::Message {
+                date,
+                audio: uuid,
+                text,
+                previous,
+                tone,
+                channel: Some(channel.clone()),
+                station: None,
+            },
+        })?;
+
+        for event in events {
+            self.web_tx.send(UiMessage::Alert {
+                idx,
+                name: channel.clone(),
+                uuid,
+                alert: event.alert,
+                matched: event.matched,
+                text: event.text,
+            })?;
+        }
+
+        Ok(())
+    }
+}
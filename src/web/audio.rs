@@ -0,0 +1,152 @@
+use std::{
+    fs::File,
+    io::{ErrorKind, Read, Seek, SeekFrom},
+};
+
+use afire::{
+    extensions::RouteShorthands, headers::ContentType, HeaderName, Request, Server, Status,
+};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::App;
+
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+pub fn attach(server: &mut Server<App>) {
+    server.get("/audio/{uuid}", |ctx| {
+        // Only a parsed UUID goes into the path, so it can't point outside the audio folder
+        let Ok(uuid) = Uuid::parse_str(ctx.param("uuid")) else {
+            ctx.status(Status::NotFound)
+                .text("Audio not found")
+                .send()?;
+            return Ok(());
+        };
+
+        let path = ctx.app().data_dir.join("audio").join(format!("{uuid}.wav"));
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                ctx.status(Status::NotFound)
+                    .text("Audio not found")
+                    .send()?;
+                return Ok(());
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        // Audio is appended to while recording, so the length is part of the tag
+        let metadata = file.metadata()?;
+        let len = metadata.len();
+        let modified = DateTime::<Utc>::from(metadata.modified()?);
+        let etag = format!("\"{len:x}-{:x}\"", modified.timestamp());
+
+        ctx.header(ContentType::new("audio/wav"))
+            .header((HeaderName::AcceptRanges, "bytes"))
+            .header(("ETag", &etag))
+            .header(("Last-Modified", modified.format(HTTP_DATE).to_string()));
+
+        if not_modified(&ctx.req, &etag, modified) {
+            ctx.status(Status::NotModified).send()?;
+            return Ok(());
+        }
+
+        // A range of an older version of the file would be garbage, and multiple ranges would
+        // need a multipart response, so both just get the whole file
+        let range = ctx
+            .req
+            .headers
+            .get(HeaderName::Range)
+            .filter(|x| !x.contains(','))
+            .filter(|_| header(&ctx.req, "If-Range").is_none_or(|x| x == etag));
+        let Some(range) = range else {
+            let mut data = Vec::with_capacity(len as usize);
+            file.read_to_end(&mut data)?;
+            ctx.bytes(data).send()?;
+            return Ok(());
+        };
+
+        match parse_range(range, len) {
+            Some((start, end)) => {
+                let mut data = vec![0; (end - start + 1) as usize];
+                file.seek(SeekFrom::Start(start))?;
+                file.read_exact(&mut data)?;
+                ctx.status(Status::PartialContent)
+                    .header((
+                        HeaderName::ContentRange,
+                        format!("bytes {start}-{end}/{len}"),
+                    ))
+                    .bytes(data)
+            }
+            None => ctx
+                .status(Status::RangeNotSatisfiable)
+                .header((HeaderName::ContentRange, format!("bytes */{len}"))),
+        }
+        .send()?;
+        Ok(())
+    });
+}
+
+/// Looks up a header afire doesn't know the name of, ignoring case.
+fn header<'a>(req: &'a Request, name: &str) -> Option<&'a str> {
+    req.headers
+        .iter()
+        .find(|x| x.name.to_string().eq_ignore_ascii_case(name))
+        .map(|x| x.value.as_ref())
+}
+
+/// If the client's cached copy is still current.
+/// `If-None-Match` takes priority over `If-Modified-Since` when both are sent.
+fn not_modified(req: &Request, etag: &str, modified: DateTime<Utc>) -> bool {
+    if let Some(tags) = header(req, "If-None-Match") {
+        return tags.split(',').any(|x| x.trim() == "*" || x.trim() == etag);
+    }
+
+    header(req, "If-Modified-Since")
+        .and_then(|x| DateTime::parse_from_rfc2822(x).ok())
+        .is_some_and(|x| modified.timestamp() <= x.timestamp())
+}
+
+/// Parses a single `bytes=` range into inclusive start and end offsets, clamped to the file.
+/// Returns `None` if the range can't be satisfied.
+fn parse_range(range: &str, len: u64) -> Option<(u64, u64)> {
+    let (start, end) = range.trim().strip_prefix("bytes=")?.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        // The last `end` bytes
+        ("", end) => {
+            let suffix = end.parse::<u64>().ok().filter(|&x| x > 0)?;
+            (len.saturating_sub(suffix), len.checked_sub(1)?)
+        }
+        (start, "") => (start.parse().ok()?, len.checked_sub(1)?),
+        (start, end) => (
+            start.parse().ok()?,
+            end.parse::<u64>().ok()?.min(len.saturating_sub(1)),
+        ),
+    };
+
+    (start <= end && start < len).then_some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_range;
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=500-", 1000), Some((500, 999)));
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=-5000", 1000), Some((0, 999)));
+        assert_eq!(parse_range("bytes=900-5000", 1000), Some((900, 999)));
+    }
+
+    #[test]
+    fn rejects_unsatisfiable_ranges() {
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=50-10", 1000), None);
+        assert_eq!(parse_range("bytes=0-", 0), None);
+        assert_eq!(parse_range("bytes=-0", 1000), None);
+        assert_eq!(parse_range("bytes=5-10", 0), None);
+        assert_eq!(parse_range("items=0-10", 1000), None);
+    }
+}
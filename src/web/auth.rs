@@ -0,0 +1,224 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use afire::{
+    extensions::RouteShorthands, middleware::MiddleResult, Header, HeaderName, Method, Middleware,
+    Request, Response, Server,
+};
+use anyhow::Result;
+use clone_macro::clone;
+use parking_lot::Mutex;
+use pbkdf2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Pbkdf2,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::App;
+use crate::config::{AuthConfig, TokenConfig};
+
+/// Routes that don't need a login, so the login page can load and post its form.
+const PUBLIC: &[&str] = &["/", "/login", "/logout"];
+/// The UI's static files, which are public so they can redirect to the login page.
+const STATIC_EXTENSIONS: &[&str] = &["html", "js", "css", "ico", "png", "svg"];
+const SESSION_COOKIE: &str = "session";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Can only push messages from another station, for `[sync]` tokens.
+    Ingest,
+    /// Can see messages and listen, but not change anything.
+    Read,
+    Admin,
+}
+
+impl Role {
+    /// Admins can do anything, everyone else only what their role is for.
+    fn allows(self, required: Role) -> bool {
+        self == required || self == Role::Admin
+    }
+}
+
+/// Checks requests against the configured users and API tokens.
+pub struct Auth {
+    config: AuthConfig,
+    /// Only send the session cookie over HTTPS.
+    secure: bool,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+struct Session {
+    role: Role,
+    expires: Instant,
+}
+
+#[derive(Deserialize)]
+struct Login {
+    name: String,
+    password: String,
+}
+
+struct AuthMiddleware(Arc<Auth>);
+
+impl Auth {
+    pub fn new(config: AuthConfig, secure: bool) -> Self {
+        Self {
+            config,
+            secure,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The role of whoever made the request, from their API token or session cookie.
+    fn role(&self, req: &Request) -> Option<Role> {
+        if bearer(req).is_some() {
+            return self.token(req).map(|x| x.role);
+        }
+
+        let id = req.cookies.get(SESSION_COOKIE)?;
+        let mut sessions = self.sessions.lock();
+        sessions.retain(|_, x| x.expires > Instant::now());
+        sessions.get(id).map(|x| x.role)
+    }
+
+    /// The API token the request was made with, if it's a known one.
+    pub fn token(&self, req: &Request) -> Option<&TokenConfig> {
+        let hash = hash_token(bearer(req)?);
+        self.config
+            .tokens
+            .iter()
+            .find(|x| x.hash.eq_ignore_ascii_case(&hash))
+    }
+
+    /// Starts a session if the password is right, returning its ID.
+    fn login(&self, login: &Login) -> Option<String> {
+        let user = self.config.users.iter().find(|x| x.name == login.name)?;
+        let hash = PasswordHash::new(&user.password).ok()?;
+        Pbkdf2
+            .verify_password(login.password.as_bytes(), &hash)
+            .ok()?;
+
+        let id = new_token();
+        let expires = Instant::now() + self.session_length();
+        self.sessions.lock().insert(
+            id.clone(),
+            Session {
+                role: user.role,
+                expires,
+            },
+        );
+        Some(id)
+    }
+
+    fn session_length(&self) -> Duration {
+        Duration::from_secs_f32(self.config.session_days * 24.0 * 60.0 * 60.0)
+    }
+
+    fn session_cookie(&self, id: &str, max_age: Duration) -> Header {
+        let secure = if self.secure { "; Secure" } else { "" };
+        Header::new(
+            "Set-Cookie",
+            format!(
+                "{SESSION_COOKIE}={id}; Max-Age={}; Path=/; HttpOnly; SameSite=Strict{secure}",
+                max_age.as_secs()
+            ),
+        )
+    }
+}
+
+impl Middleware for AuthMiddleware {
+    fn pre(&self, req: &mut Request) -> MiddleResult {
+        let Some(required) = required_role(req) else {
+            return MiddleResult::Continue;
+        };
+
+        match self.0.role(req) {
+            Some(role) if role.allows(required) => MiddleResult::Continue,
+            Some(_) => MiddleResult::Send(
+                Response::new()
+                    .status(403)
+                    .text("Not allowed for your role"),
+            ),
+            None => MiddleResult::Send(Response::new().status(401).text("Login required")),
+        }
+    }
+}
+
+fn required_role(req: &Request) -> Option<Role> {
+    let path = req.path.as_str();
+    let is_static = path
+        .rsplit_once('.')
+        .is_some_and(|(_, ext)| STATIC_EXTENSIONS.contains(&ext));
+    if PUBLIC.contains(&path) || is_static {
+        return None;
+    }
+
+    Some(if path.starts_with("/ingest/") {
+        Role::Ingest
+    } else {
+        match req.method {
+            Method::GET | Method::HEAD => Role::Read,
+            _ => Role::Admin,
+        }
+    })
+}
+
+pub fn attach(server: &mut Server<App>, auth: Arc<Auth>) {
+    AuthMiddleware(auth.clone()).attach(server);
+
+    server.post(
+        "/login",
+        clone!([auth], move |ctx| {
+            let login = serde_json::from_slice::<Login>(&ctx.req.body)?;
+            match auth.login(&login) {
+                Some(id) => ctx
+                    .header(auth.session_cookie(&id, auth.session_length()))
+                    .text("Logged in"),
+                None => ctx.status(401).text("Wrong name or password"),
+            }
+            .send()?;
+            Ok(())
+        }),
+    );
+
+    server.post("/logout", move |ctx| {
+        if let Some(id) = ctx.req.cookies.get(SESSION_COOKIE) {
+            auth.sessions.lock().remove(id);
+        }
+
+        ctx.header(auth.session_cookie("", Duration::ZERO))
+            .text("Logged out")
+            .send()?;
+        Ok(())
+    });
+}
+
+fn bearer(req: &Request) -> Option<&str> {
+    req.headers
+        .get(HeaderName::Authorization)
+        .and_then(|x| x.strip_prefix("Bearer "))
+}
+
+/// Hashes a password for a user in the config.
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::encode_b64(Uuid::new_v4().as_bytes())?;
+    Ok(Pbkdf2
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+/// API tokens are random, so a fast hash is enough to keep them out of the config.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token))
+}
+
+/// A random 64 character token, used for both API tokens and session IDs.
+pub fn new_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
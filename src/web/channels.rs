@@ -0,0 +1,69 @@
+use afire::{error::AnyResult, extensions::RouteShorthands, Content, Context, Server};
+use anyhow::{bail, Result};
+use serde_json::{json, Map, Value};
+
+use super::App;
+use crate::config::{ChannelConfig, Config};
+
+/// Routes for listing and editing channels, which are saved back to the config file and applied live.
+pub fn attach(server: &mut Server<App>) {
+    server.get("/channels", |ctx| {
+        let config = ctx.app().reloader.current();
+        ctx.text(json!(config.channels))
+            .content(Content::JSON)
+            .send()?;
+        Ok(())
+    });
+
+    server.post("/channels", |ctx| {
+        edit_channels(ctx, |config| {
+            let channel = serde_json::from_slice::<ChannelConfig>(&ctx.req.body)?;
+            config.channels.push(channel);
+            Ok(())
+        })
+    });
+
+    // Fields left out of the body keep their current values
+    server.put("/channels/{idx}", |ctx| {
+        edit_channels(ctx, |config| {
+            let idx = index(config, ctx)?;
+            let channel = &mut config.channels[idx];
+            let changes = serde_json::from_slice::<Map<String, Value>>(&ctx.req.body)?;
+
+            let mut value = serde_json::to_value(&*channel)?;
+            value.as_object_mut().unwrap().extend(changes);
+            *channel = serde_json::from_value(value)?;
+            Ok(())
+        })
+    });
+
+    server.delete("/channels/{idx}", |ctx| {
+        edit_channels(ctx, |config| {
+            config.channels.remove(index(config, ctx)?);
+            Ok(())
+        })
+    });
+}
+
+/// Saves and applies the edit, responding with the new channels or why the edit was rejected.
+fn edit_channels(ctx: &Context<App>, edit: impl FnOnce(&mut Config) -> Result<()>) -> AnyResult {
+    let result = ctx.app().reloader.update(|config| {
+        edit(config)?;
+        Ok(config.channels.clone())
+    });
+
+    match result {
+        Ok(channels) => ctx.text(json!(channels)).content(Content::JSON),
+        Err(err) => ctx.status(400).text(err),
+    }
+    .send()?;
+    Ok(())
+}
+
+fn index(config: &Config, ctx: &Context<App>) -> Result<usize> {
+    let idx = ctx.param("idx").parse::<usize>()?;
+    if idx >= config.channels.len() {
+        bail!("No channel at index {idx}");
+    }
+    Ok(idx)
+}
@@ -3,10 +3,18 @@ use std::{fs, path::Path, sync::Arc};
 use anyhow::Result;
 use chrono::NaiveDateTime;
 use parking_lot::{Mutex, MutexGuard};
-use rusqlite::{params, Connection};
-use serde::Serialize;
+use rusqlite::{params, Connection, DatabaseName, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// Schema changes applied in order on top of `init_messages.sql`, tracked with `PRAGMA user_version`.
+const MIGRATIONS: &[&str] = &[
+    include_str!("sql/migrations/01_previous.sql"),
+    include_str!("sql/migrations/02_tone.sql"),
+    include_str!("sql/migrations/03_channel.sql"),
+    include_str!("sql/migrations/04_sync.sql"),
+];
+
 #[derive(Clone)]
 pub struct Database {
     connection: Arc<Mutex<Connection>>,
@@ -16,11 +24,28 @@ pub struct LockedDatabase<'a> {
     connection: MutexGuard<'a, Connection>,
 }
 
-#[derive(Clone, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Message {
     pub date: NaiveDateTime,
     pub audio: Uuid,
     pub text: Option<String>,
+    /// The message this one continues, if it was split for exceeding its channel's `max_duration`.
+    pub previous: Option<Uuid>,
+    /// The CTCSS tone or DCS code heard during the message.
+    pub tone: Option<String>,
+    /// Name of the channel it was heard on, missing for messages from before this was recorded.
+    pub channel: Option<String>,
+    /// The station that heard it, if it was synced from another server.
+    pub station: Option<String>,
+}
+
+/// Which messages to export. Dates are in UTC, like the ones in the database.
+#[derive(Default, Serialize)]
+pub struct Filter {
+    pub from: Option<NaiveDateTime>,
+    /// Exclusive.
+    pub to: Option<NaiveDateTime>,
+    pub channel: Option<String>,
 }
 
 impl Database {
@@ -30,12 +55,19 @@ impl Database {
         let connection = Connection::open(data_dir.join("data.db"))?;
         connection.execute(include_str!("sql/init_messages.sql"), params![])?;
 
+        let version: usize =
+            connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        for (idx, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            connection.execute_batch(migration)?;
+            connection.pragma_update(None, "user_version", idx + 1)?;
+        }
+
         Ok(Self {
             connection: Arc::new(Mutex::new(connection)),
         })
     }
 
-    pub fn lock(&self) -> LockedDatabase {
+    pub fn lock(&self) -> LockedDatabase<'_> {
         LockedDatabase {
             connection: self.connection.lock(),
         }
@@ -43,9 +75,18 @@ impl Database {
 }
 
 impl<'a> LockedDatabase<'a> {
-    pub fn insert_message(&self, text: Option<&str>, audio: Uuid) -> Result<()> {
-        self.connection
-            .execute(include_str!("sql/insert_message.sql"), params![text, audio])?;
+    pub fn insert_message(
+        &self,
+        text: Option<&str>,
+        audio: Uuid,
+        previous: Option<Uuid>,
+        tone: Option<&str>,
+        channel: &str,
+    ) -> Result<()> {
+        self.connection.execute(
+            include_str!("sql/insert_message.sql"),
+            params![text, audio, previous, tone, channel],
+        )?;
         Ok(())
     }
 
@@ -54,14 +95,84 @@ impl<'a> LockedDatabase<'a> {
             .connection
             .prepare(include_str!("sql/get_messages.sql"))?;
         let messages = statement
-            .query_map(params![], |row| {
-                Ok(Message {
-                    date: row.get(0)?,
-                    audio: row.get(1)?,
-                    text: row.get(2)?,
-                })
-            })?
+            .query_map(params![], message)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(messages)
+    }
+
+    /// Adds a message from another archive, unless one with the same audio is already here.
+    /// Returns if it was added.
+    pub fn import_message(&self, message: &Message) -> Result<bool> {
+        let added = self.connection.execute(
+            include_str!("sql/import_message.sql"),
+            params![
+                message.date,
+                message.text,
+                message.audio,
+                message.previous,
+                message.tone,
+                message.channel,
+                message.station
+            ],
+        )?;
+        Ok(added > 0)
+    }
+
+    /// Queues a message to be pushed to the central server.
+    pub fn queue_sync(&self, audio: Uuid) -> Result<()> {
+        self.connection
+            .execute(include_str!("sql/queue_sync.sql"), params![audio])?;
+        Ok(())
+    }
+
+    /// The oldest message waiting to be pushed to the central server.
+    pub fn next_sync(&self) -> Result<Option<Message>> {
+        let mut statement = self.connection.prepare(include_str!("sql/next_sync.sql"))?;
+        Ok(statement.query_row(params![], message).optional()?)
+    }
+
+    pub fn remove_sync(&self, audio: Uuid) -> Result<()> {
+        self.connection
+            .execute(include_str!("sql/remove_sync.sql"), params![audio])?;
+        Ok(())
+    }
+
+    /// Messages waiting to be sent to the central server.
+    pub fn count_sync(&self) -> Result<u64> {
+        let count =
+            self.connection
+                .query_row(include_str!("sql/count_sync.sql"), params![], |row| {
+                    row.get(0)
+                })?;
+        Ok(count)
+    }
+
+    /// Copies the database to `path` with SQLite's backup API, so it can keep being written to.
+    pub fn backup(&self, path: &Path) -> Result<()> {
+        self.connection.backup(DatabaseName::Main, path, None)?;
+        Ok(())
+    }
+
+    /// Messages matching the filter, oldest first.
+    pub fn export_messages(&self, filter: &Filter) -> Result<Vec<Message>> {
+        let mut statement = self
+            .connection
+            .prepare(include_str!("sql/export_messages.sql"))?;
+        let messages = statement
+            .query_map(params![filter.from, filter.to, filter.channel], message)?
             .collect::<rusqlite::Result<Vec<_>>>()?;
         Ok(messages)
     }
 }
+
+fn message(row: &Row) -> rusqlite::Result<Message> {
+    Ok(Message {
+        date: row.get(0)?,
+        audio: row.get(1)?,
+        text: row.get(2)?,
+        previous: row.get(3)?,
+        tone: row.get(4)?,
+        channel: row.get(5)?,
+        station: row.get(6)?,
+    })
+}
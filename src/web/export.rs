@@ -0,0 +1,49 @@
+use std::{io, thread};
+
+use afire::{extensions::RouteShorthands, headers::ContentType, Server};
+use log::error;
+
+use super::{database::Filter, App};
+use crate::export::{self, Format};
+
+pub fn attach(server: &mut Server<App>) {
+    server.get("/export/{format}", |ctx| {
+        let query = &ctx.req.query;
+        let parsed = ctx.param("format").parse::<Format>().and_then(|format| {
+            let filter = Filter::parse(query.get("from"), query.get("to"), query.get("channel"))?;
+            Ok((format, filter))
+        });
+        let (format, filter) = match parsed {
+            Ok(x) => x,
+            Err(err) => {
+                ctx.status(400).text(err).send()?;
+                return Ok(());
+            }
+        };
+
+        let app = ctx.app();
+        let messages = app.database.lock().export_messages(&filter)?;
+        ctx.header(ContentType::new(format.content_type())).header((
+            "Content-Disposition",
+            format!("attachment; filename=\"messages.{}\"", format.extension()),
+        ));
+
+        // Bundles can be large, so they're streamed as they're written instead of built in memory
+        if format == Format::Zip {
+            let (reader, writer) = io::pipe()?;
+            let data_dir = app.data_dir.clone();
+            thread::spawn(move || {
+                if let Err(err) = export::write(format, &messages, &filter, &data_dir, writer) {
+                    error!("Export failed: {err:?}");
+                }
+            });
+            ctx.stream(reader).send()?;
+        } else {
+            let mut out = Vec::new();
+            export::write(format, &messages, &filter, &app.data_dir, &mut out)?;
+            ctx.bytes(out).send()?;
+        }
+
+        Ok(())
+    });
+}
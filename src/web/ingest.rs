@@ -0,0 +1,91 @@
+use std::{fs, io::ErrorKind, path::Path, sync::Arc};
+
+use afire::{extensions::RouteShorthands, Server};
+use anyhow::Result;
+use uuid::Uuid;
+
+use super::{auth::Auth, database::Message, App};
+
+/// Receives messages pushed by other stations with `[sync]` configured, each using a token named
+/// after the station.
+pub fn attach(server: &mut Server<App>, auth: Arc<Auth>) {
+    server.put("/ingest/audio/{uuid}", |ctx| {
+        let Ok(uuid) = Uuid::parse_str(ctx.param("uuid")) else {
+            ctx.status(400).text("Invalid UUID").send()?;
+            return Ok(());
+        };
+
+        let dir = ctx.app().data_dir.join("audio");
+        match save_audio(&dir, uuid, &ctx.req.body)? {
+            true => ctx.text("Saved"),
+            false => ctx.status(409).text("Already have it"),
+        }
+        .send()?;
+        Ok(())
+    });
+
+    server.post("/ingest/message", move |ctx| {
+        let message = serde_json::from_slice::<Message>(&ctx.req.body)?;
+        let Some(station) = message.station.as_deref().filter(|x| !x.trim().is_empty()) else {
+            ctx.status(400).text("Missing station").send()?;
+            return Ok(());
+        };
+        // So one station's token can't be used to post as another
+        if auth.token(&ctx.req).is_none_or(|x| x.name != station) {
+            ctx.status(403)
+                .text(format!(
+                    "Station `{station}` must use a token with its name"
+                ))
+                .send()?;
+            return Ok(());
+        }
+
+        match ctx.app().database.lock().import_message(&message)? {
+            true => ctx.text("Added"),
+            false => ctx.text("Already have it"),
+        }
+        .send()?;
+        Ok(())
+    });
+}
+
+/// Saves a message's audio, returning false without touching it if there already is some.
+fn save_audio(dir: &Path, uuid: Uuid, data: &[u8]) -> Result<bool> {
+    // Written to the side first, so a half received file is never served. Linking it into place
+    // fails if the file exists, where renaming would replace it.
+    let path = dir.join(format!("{uuid}.wav"));
+    let partial = path.with_extension("wav.part");
+    fs::write(&partial, data)?;
+    let linked = fs::hard_link(&partial, &path);
+    fs::remove_file(&partial)?;
+
+    match linked {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == ErrorKind::AlreadyExists => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use uuid::Uuid;
+
+    use super::save_audio;
+
+    #[test]
+    fn audio_is_not_replaced() {
+        let dir = env::temp_dir().join(format!("radio-history-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let uuid = Uuid::new_v4();
+        let path = dir.join(format!("{uuid}.wav"));
+        assert!(save_audio(&dir, uuid, b"first").unwrap());
+        assert!(!save_audio(&dir, uuid, b"second").unwrap());
+        assert_eq!(fs::read(&path).unwrap(), b"first");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
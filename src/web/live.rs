@@ -0,0 +1,71 @@
+use std::{sync::Arc, time::Duration};
+
+use afire::{extensions::RouteShorthands, prelude::WebSocketExt, Server};
+use flume::{RecvTimeoutError, Sender};
+use parking_lot::Mutex;
+
+use super::App;
+
+/// How often a listener on a quiet channel is checked for having left.
+const HEARTBEAT: Duration = Duration::from_secs(5);
+
+/// Forwards demodulated audio to anyone listening live, as frames of 16 bit little endian PCM at
+/// [`crate::consts::WAVE_SAMPLE_RATE`].
+#[derive(Clone, Default)]
+pub struct LiveAudio {
+    listeners: Arc<Mutex<Vec<Listener>>>,
+}
+
+struct Listener {
+    channel: String,
+    tx: Sender<Vec<u8>>,
+}
+
+impl LiveAudio {
+    /// Includes listeners that have left in the last [`HEARTBEAT`].
+    pub fn listeners(&self) -> usize {
+        self.listeners.lock().len()
+    }
+
+    /// Sends a chunk of a channel's audio to its listeners, forgetting any that have disconnected.
+    pub fn send(&self, channel: &str, audio: &[f32]) {
+        let mut listeners = self.listeners.lock();
+        if !listeners.iter().any(|x| x.channel == channel) {
+            return;
+        }
+
+        let frame = audio
+            .iter()
+            .flat_map(|x| ((x.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes())
+            .collect::<Vec<_>>();
+        listeners.retain(|x| x.channel != channel || x.tx.send(frame.clone()).is_ok());
+    }
+}
+
+pub fn attach(server: &mut Server<App>) {
+    server.get("/live", |ctx| {
+        let Some(channel) = ctx.req.query.get("channel") else {
+            ctx.status(400).text("Missing channel").send()?;
+            return Ok(());
+        };
+        let channel = channel.to_owned();
+        let socket = ctx.ws()?;
+
+        let (tx, rx) = flume::unbounded();
+        let listeners = ctx.app().live.listeners.clone();
+        listeners.lock().push(Listener { channel, tx });
+
+        // Wake up now and then so a listener on a quiet channel doesn't hold the worker forever.
+        loop {
+            match rx.recv_timeout(HEARTBEAT) {
+                Ok(frame) if socket.is_open() => socket.send_binary(frame),
+                Err(RecvTimeoutError::Timeout) if socket.is_open() => {}
+                _ => break,
+            }
+        }
+
+        drop(rx);
+        listeners.lock().retain(|x| !x.tx.is_disconnected());
+        Ok(())
+    });
+}
@@ -1,56 +1,189 @@
-use std::{fs::File, sync::Arc, thread};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
 
 use afire::{
     extensions::{RouteShorthands, ServeStatic},
     headers::ContentType,
     prelude::WebSocketExt,
-    trace::{self, Level},
     Content, Middleware, Server,
 };
+use anyhow::Result;
 use clone_macro::clone;
 use flume::Sender;
+use log::error;
 use parking_lot::Mutex;
 use serde::Serialize;
 use serde_json::json;
 use uuid::Uuid;
 
-use crate::config::ServerConfig;
+use crate::{
+    config::Config,
+    metrics::{Exposition, Metrics},
+    mqtt,
+    reload::Reloader,
+};
 
+mod audio;
+pub mod auth;
+mod channels;
 pub mod database;
+mod export;
+mod ingest;
+mod live;
+mod spectrum;
+pub mod tls;
+use auth::Auth;
 use database::{Database, Message};
+pub use live::LiveAudio;
+pub use spectrum::{ChannelMarker, SpectrumFeed, SpectrumRow};
+use tls::TlsEventLoop;
+
+/// How long the audio directory's size is cached for, as measuring it stats every recording.
+const DISK_USAGE_INTERVAL: Duration = Duration::from_secs(60);
 
 pub struct App {
     database: Database,
+    metrics: Arc<Metrics>,
+    reloader: Reloader,
+    data_dir: PathBuf,
+    live: LiveAudio,
+    spectrum: SpectrumFeed,
     clients: Arc<Mutex<Vec<Sender<UiMessage>>>>,
+    /// Latest gain and device state of each radio, replayed to new `/events` clients.
+    /// Only changed while holding `clients`, so none are missed or sent twice.
+    retained: Arc<Mutex<BTreeMap<String, UiMessage>>>,
+    /// Size of the audio directory and when it was measured.
+    audio_size: Mutex<Option<(Instant, u64)>>,
 }
 
+/// Events pushed to the web UI. Channel events carry the channel's index and name,
+/// and the UUID of the message being recorded, if any.
 #[derive(Clone, Serialize)]
 #[serde(tag = "type")]
 pub enum UiMessage {
-    Receiving { idx: u32, name: String },
-    Processing { idx: u32 },
-    Complete(Message),
+    Receiving {
+        idx: u32,
+        name: String,
+        uuid: Uuid,
+    },
+    Processing {
+        idx: u32,
+        name: String,
+        uuid: Uuid,
+    },
+    Discarded {
+        idx: u32,
+        name: String,
+        uuid: Uuid,
+    },
+    Complete {
+        idx: u32,
+        name: String,
+        uuid: Uuid,
+        #[serde(flatten)]
+        message: Message,
+    },
+    /// Sent periodically for every channel currently being listened to.
+    Level {
+        idx: u32,
+        name: String,
+        uuid: Option<Uuid>,
+        rms: f32,
+        noise_floor: f32,
+        open: bool,
+    },
+    /// A transcript matched one of the configured alerts.
+    Alert {
+        idx: u32,
+        name: String,
+        uuid: Uuid,
+        alert: String,
+        matched: String,
+        text: String,
+    },
+    TunerGain {
+        radio: String,
+        gain: f32,
+    },
+    /// A radio's device failed or stalled and is being reopened, or has come back.
+    Device {
+        radio: String,
+        connected: bool,
+        error: Option<String>,
+    },
 }
 
-pub fn start(server_config: &ServerConfig, database: Database) -> Sender<UiMessage> {
-    trace::set_log_level(Level::Trace);
-
+pub fn start(
+    config: &Config,
+    database: Database,
+    metrics: Arc<Metrics>,
+    reloader: Reloader,
+    live: LiveAudio,
+    spectrum: SpectrumFeed,
+) -> Result<Sender<UiMessage>> {
     let (tx, rx) = flume::unbounded::<UiMessage>();
     let clients = Arc::new(Mutex::new(Vec::<Sender<_>>::new()));
+    let retained = Arc::new(Mutex::new(BTreeMap::new()));
+    let mqtt = config.mqtt.clone().map(mqtt::start);
 
-    thread::spawn(clone!([clients], move || {
+    thread::spawn(clone!([clients, retained], move || {
         for message in rx.iter() {
-            for client in clients.lock().iter() {
-                client.send(message.clone()).unwrap();
+            if let Some(mqtt) = &mqtt {
+                let _ = mqtt.send(message.clone());
+            }
+
+            let mut clients = clients.lock();
+            let key = match &message {
+                UiMessage::TunerGain { radio, .. } => Some(format!("gain/{radio}")),
+                UiMessage::Device { radio, .. } => Some(format!("device/{radio}")),
+                _ => None,
+            };
+            if let Some(key) = key {
+                retained.lock().insert(key, message.clone());
             }
+            clients.retain(|x| x.send(message.clone()).is_ok());
         }
     }));
 
+    let server_config = &config.server;
     let mut server = Server::<App>::new(&server_config.host, server_config.port)
         .workers(server_config.workers)
-        .state(App { database, clients });
+        .state(App {
+            database,
+            metrics,
+            reloader,
+            data_dir: config.misc.data_dir.clone(),
+            live,
+            spectrum,
+            clients,
+            retained,
+            audio_size: Mutex::new(None),
+        });
+    if let Some(tls) = &server_config.tls {
+        server = server.event_loop(TlsEventLoop::new(tls.clone())?);
+    }
 
     ServeStatic::new("web").attach(&mut server);
+    let secure = server_config.tls.is_some();
+    let auth = config.auth.clone().map(|x| Arc::new(Auth::new(x, secure)));
+    if let Some(auth) = &auth {
+        auth::attach(&mut server, auth.clone());
+    }
+    audio::attach(&mut server);
+    channels::attach(&mut server);
+    export::attach(&mut server);
+    // Validation makes sure ingest is only on with auth, which it needs to know who's sending
+    if let Some(auth) = auth.filter(|_| server_config.ingest) {
+        ingest::attach(&mut server, auth);
+    }
+    live::attach(&mut server);
+    spectrum::attach(&mut server);
 
     server.get("/messages", |ctx| {
         let messages = ctx.app().database.lock().get_messages()?;
@@ -58,29 +191,113 @@ pub fn start(server_config: &ServerConfig, database: Database) -> Sender<UiMessa
         Ok(())
     });
 
-    server.get("/audio/{uuid}", |ctx| {
-        let uuid = Uuid::parse_str(ctx.param("uuid"))?;
-        let path = format!("data/audio/{uuid}.wav");
-        ctx.stream(File::open(path)?)
-            .header(ContentType::new("audio/wav"))
+    server.get("/metrics", |ctx| {
+        let app = ctx.app();
+        let mut out = Exposition::default();
+        app.metrics.write(&mut out);
+
+        out.metric(
+            "sync_queue",
+            "gauge",
+            "Messages waiting to be sent to the central server.",
+            app.database.lock().count_sync()? as f64,
+        );
+        out.labeled(
+            "disk_usage_bytes",
+            "gauge",
+            "Size of the data directory.",
+            "kind",
+            [
+                ("audio", app.audio_size()? as f64),
+                (
+                    "database",
+                    fs::metadata(app.data_dir.join("data.db"))?.len() as f64,
+                ),
+            ],
+        );
+        out.labeled(
+            "websocket_clients",
+            "gauge",
+            "Connected WebSocket clients.",
+            "feed",
+            [
+                ("events", app.clients.lock().len() as f64),
+                ("live", app.live.listeners() as f64),
+                ("spectrum", app.spectrum.listeners() as f64),
+            ],
+        );
+
+        ctx.text(out.finish())
+            .header(ContentType::new("text/plain; version=0.0.4"))
             .send()?;
         Ok(())
     });
 
+    server.post("/config/reload", |ctx| {
+        match ctx.app().reloader.reload() {
+            Ok(()) => ctx.text("Config reloaded"),
+            Err(err) => ctx.status(400).text(err),
+        }
+        .send()?;
+        Ok(())
+    });
+
     server.get("/events", |ctx| {
         let socket = ctx.ws()?;
 
         let (tx, rx) = flume::unbounded();
-        ctx.app().clients.lock().push(tx);
+        let app = ctx.app();
+        let mut clients = app.clients.lock();
+        for message in app.retained.lock().values() {
+            let _ = tx.send(message.clone());
+        }
+        clients.push(tx);
+        drop(clients);
 
         for message in rx.iter() {
+            if !socket.is_open() {
+                break;
+            }
             socket.send(json!(message));
         }
 
         Ok(())
     });
 
-    thread::spawn(|| server.run().unwrap());
+    thread::spawn(move || {
+        if let Err(err) = server.run() {
+            error!("Web server stopped: {err}");
+        }
+    });
+
+    Ok(tx)
+}
+
+impl App {
+    /// Size of the audio directory, measured at most once every [`DISK_USAGE_INTERVAL`].
+    fn audio_size(&self) -> Result<u64> {
+        let mut cached = self.audio_size.lock();
+        if let Some((measured, size)) = *cached {
+            if measured.elapsed() < DISK_USAGE_INTERVAL {
+                return Ok(size);
+            }
+        }
+
+        let size = dir_size(&self.data_dir.join("audio"))?;
+        *cached = Some((Instant::now(), size));
+        Ok(size)
+    }
+}
+
+/// Total size of the files in a directory, not counting subdirectories.
+fn dir_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let metadata = entry?.metadata()?;
+        if metadata.is_file() {
+            size += metadata.len();
+        }
+    }
 
-    tx
+    Ok(size)
 }
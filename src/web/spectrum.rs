@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use afire::{extensions::RouteShorthands, prelude::WebSocketExt, Server};
+use flume::Sender;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::json;
+
+use super::App;
+
+/// Pushes spectrum rows to the waterfall viewers, if there are any.
+#[derive(Clone, Default)]
+pub struct SpectrumFeed {
+    listeners: Arc<Mutex<Vec<Sender<Arc<String>>>>>,
+}
+
+#[derive(Serialize)]
+pub struct SpectrumRow {
+    /// Name of the radio it's from.
+    pub radio: String,
+    pub center_freq: u32,
+    pub sample_rate: u32,
+    /// Width of the band each channel is filtered to, in Hz.
+    pub channel_width: u32,
+    /// Power of each bin in whole dB, from the lowest to highest frequency.
+    pub bins: Vec<i8>,
+    pub channels: Vec<ChannelMarker>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ChannelMarker {
+    pub idx: u32,
+    pub name: String,
+    pub freq: u32,
+    pub rms: f32,
+    pub squelch: f32,
+    pub open: bool,
+}
+
+impl SpectrumFeed {
+    /// If anyone is watching, so the FFT can be skipped when nobody is.
+    pub fn listening(&self) -> bool {
+        !self.listeners.lock().is_empty()
+    }
+
+    pub fn listeners(&self) -> usize {
+        self.listeners.lock().len()
+    }
+
+    pub fn send(&self, row: &SpectrumRow) {
+        let row = Arc::new(json!(row).to_string());
+        self.listeners
+            .lock()
+            .retain(|x| x.send(row.clone()).is_ok());
+    }
+}
+
+pub fn attach(server: &mut Server<App>) {
+    server.get("/spectrum", |ctx| {
+        let socket = ctx.ws()?;
+
+        let (tx, rx) = flume::unbounded();
+        ctx.app().spectrum.listeners.lock().push(tx);
+
+        for row in rx.iter() {
+            if !socket.is_open() {
+                break;
+            }
+            socket.send(row);
+        }
+
+        Ok(())
+    });
+}
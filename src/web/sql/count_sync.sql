@@ -0,0 +1 @@
+SELECT COUNT(*) FROM sync_queue;
\ No newline at end of file
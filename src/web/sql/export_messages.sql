@@ -0,0 +1,6 @@
+SELECT date, audio, text, previous, tone, channel, station
+FROM messages
+WHERE ($1 IS NULL OR date >= $1)
+    AND ($2 IS NULL OR date < $2)
+    AND ($3 IS NULL OR channel = $3)
+ORDER BY date;
\ No newline at end of file
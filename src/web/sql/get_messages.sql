@@ -1,3 +1,3 @@
-SELECT *
+SELECT date, audio, text, previous, tone, channel, station
 FROM messages
 ORDER BY date DESC;
\ No newline at end of file
@@ -0,0 +1,3 @@
+INSERT INTO messages (date, text, audio, previous, tone, channel, station)
+SELECT $1, $2, $3, $4, $5, $6, $7
+WHERE NOT EXISTS (SELECT 1 FROM messages WHERE audio = $3);
\ No newline at end of file
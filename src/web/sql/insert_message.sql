@@ -1,2 +1,2 @@
-INSERT INTO messages (text, audio)
-VALUES ($1, $2);
\ No newline at end of file
+INSERT INTO messages (text, audio, previous, tone, channel)
+VALUES ($1, $2, $3, $4, $5);
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE messages ADD COLUMN previous BLOB;
\ No newline at end of file
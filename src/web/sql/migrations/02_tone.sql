@@ -0,0 +1 @@
+ALTER TABLE messages ADD COLUMN tone TEXT;
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE messages ADD COLUMN channel TEXT;
\ No newline at end of file
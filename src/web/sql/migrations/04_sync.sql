@@ -0,0 +1,5 @@
+ALTER TABLE messages ADD COLUMN station TEXT;
+
+CREATE TABLE IF NOT EXISTS sync_queue (
+    audio BLOB PRIMARY KEY
+);
\ No newline at end of file
@@ -0,0 +1,5 @@
+SELECT date, messages.audio, text, previous, tone, channel, station
+FROM sync_queue
+    JOIN messages ON messages.audio = sync_queue.audio
+ORDER BY sync_queue.rowid
+LIMIT 1;
\ No newline at end of file
@@ -0,0 +1,2 @@
+INSERT OR IGNORE INTO sync_queue (audio)
+VALUES ($1);
\ No newline at end of file
@@ -0,0 +1,2 @@
+DELETE FROM sync_queue
+WHERE audio = $1;
\ No newline at end of file
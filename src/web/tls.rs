@@ -0,0 +1,214 @@
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, SystemTime},
+};
+
+use afire::{
+    internal::{
+        event_loop::EventLoop,
+        handle::handle,
+        socket::{Socket, SocketStream, Stream},
+    },
+    Server,
+};
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use parking_lot::Mutex;
+use rustls::{
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    ServerConfig, ServerConnection,
+};
+
+use crate::{config::TlsConfig, reload::modified};
+
+/// Accepts connections like afire's default event loop, but wraps them in TLS.
+/// The certificate is reloaded when its files change, so renewals don't need a restart.
+pub struct TlsEventLoop {
+    config: TlsConfig,
+    certificate: Mutex<Certificate>,
+}
+
+struct Certificate {
+    modified: [Option<SystemTime>; 2],
+    server_config: Arc<ServerConfig>,
+}
+
+/// A TLS connection that can be read from and written to at the same time from different clones,
+/// which afire needs for websockets.
+/// The lock is only held while moving data through rustls, never while waiting on the socket.
+struct TlsStream {
+    socket: TcpStream,
+    connection: Arc<Mutex<ServerConnection>>,
+}
+
+/// Loads the certificate chain and private key into a rustls config.
+pub fn load(config: &TlsConfig) -> Result<ServerConfig> {
+    let certs = CertificateDer::pem_file_iter(&config.cert)
+        .and_then(|x| x.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("reading certificates from `{}`", config.cert.display()))?;
+    let key = PrivateKeyDer::from_pem_file(&config.key)
+        .with_context(|| format!("reading private key from `{}`", config.key.display()))?;
+
+    Ok(ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?)
+}
+
+impl TlsEventLoop {
+    pub fn new(config: TlsConfig) -> Result<Self> {
+        let certificate = Certificate {
+            modified: Self::modified(&config),
+            server_config: Arc::new(load(&config)?),
+        };
+
+        Ok(Self {
+            config,
+            certificate: Mutex::new(certificate),
+        })
+    }
+
+    fn modified(config: &TlsConfig) -> [Option<SystemTime>; 2] {
+        [modified(&config.cert), modified(&config.key)]
+    }
+
+    /// The current rustls config, reloading it first if the files have changed.
+    /// If the new files can't be loaded (for example mid-renewal) the old certificate is kept.
+    fn server_config(&self) -> Arc<ServerConfig> {
+        let mut certificate = self.certificate.lock();
+        let modified = Self::modified(&self.config);
+        if modified != certificate.modified {
+            match load(&self.config) {
+                Ok(config) => {
+                    info!("Reloaded TLS certificate");
+                    certificate.modified = modified;
+                    certificate.server_config = Arc::new(config);
+                }
+                Err(err) => warn!("Keeping previous TLS certificate: {err:#}"),
+            }
+        }
+
+        certificate.server_config.clone()
+    }
+}
+
+impl<State: Send + Sync + 'static> EventLoop<State> for TlsEventLoop {
+    fn run(&self, server: Arc<Server<State>>, addr: SocketAddr) -> afire::error::Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        for stream in listener.incoming() {
+            if !server.running.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    warn!("Error accepting connection: {err}");
+                    continue;
+                }
+            };
+
+            let connection = match ServerConnection::new(self.server_config()) {
+                Ok(connection) => connection,
+                Err(err) => {
+                    debug!("Error starting TLS connection: {err}");
+                    continue;
+                }
+            };
+
+            let socket = Arc::new(Socket::new(TlsStream {
+                socket: stream,
+                connection: Arc::new(Mutex::new(connection)),
+            }));
+            let this_server = server.clone();
+            server
+                .thread_pool
+                .execute(move || handle(socket, this_server));
+        }
+
+        Ok(())
+    }
+}
+
+impl TlsStream {
+    /// Sends anything rustls has queued, like handshake messages or encrypted data.
+    fn write_tls(&self, connection: &mut ServerConnection) -> io::Result<()> {
+        while connection.wants_write() {
+            connection.write_tls(&mut &self.socket)?;
+        }
+        Ok(())
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut encrypted = [0; 16 * 1024];
+        loop {
+            {
+                let mut connection = self.connection.lock();
+                self.write_tls(&mut connection)?;
+                match connection.reader().read(buf) {
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                    result => return result,
+                }
+            }
+
+            let len = (&self.socket).read(&mut encrypted)?;
+            if len == 0 {
+                return Ok(0);
+            }
+
+            let mut connection = self.connection.lock();
+            let mut data = &encrypted[..len];
+            while !data.is_empty() {
+                connection.read_tls(&mut data)?;
+                if let Err(err) = connection.process_new_packets() {
+                    // Let the client know why, if we can
+                    let _ = self.write_tls(&mut connection);
+                    return Err(io::Error::new(ErrorKind::InvalidData, err));
+                }
+            }
+        }
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut connection = self.connection.lock();
+        let len = connection.writer().write(buf)?;
+        self.write_tls(&mut connection)?;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut connection = self.connection.lock();
+        connection.writer().flush()?;
+        self.write_tls(&mut connection)
+    }
+}
+
+impl Stream for TlsStream {
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.peer_addr()
+    }
+
+    fn try_clone(&self) -> io::Result<SocketStream> {
+        Ok(Box::new(TlsStream {
+            socket: self.socket.try_clone()?,
+            connection: self.connection.clone(),
+        }))
+    }
+
+    fn shutdown(&self, shutdown: Shutdown) -> io::Result<()> {
+        let mut connection = self.connection.lock();
+        connection.send_close_notify();
+        let _ = self.write_tls(&mut connection);
+        self.socket.shutdown(shutdown)
+    }
+
+    fn set_timeout(&self, duration: Option<Duration>) -> io::Result<()> {
+        self.socket.set_read_timeout(duration)?;
+        self.socket.set_write_timeout(duration)
+    }
+}
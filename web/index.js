@@ -1,36 +1,187 @@
+// Messages can come from other stations, so their fields are only ever set as text
 function add_message(message, top) {
   let tr = document.createElement("tr");
-  tr.innerHTML = `
-            <td>${message.date}</td>
-            <td>${message.text}</td>
-            <td class="center"><a href="/audio/${message.audio}">▶</a></td>
-        `;
+  let cell = (text) => {
+    let td = document.createElement("td");
+    td.textContent = text;
+    tr.appendChild(td);
+    return td;
+  };
+
+  cell(message.date);
+  cell(`${message.station ? `${message.station} / ` : ""}${message.channel ?? ""}`);
+  cell(`${message.previous ? "… " : ""}${message.text ?? ""}`);
+  cell(message.tone ?? "");
+
+  let link = document.createElement("a");
+  link.href = `/audio/${encodeURIComponent(message.audio)}`;
+  link.textContent = "▶";
+  let play = cell("");
+  play.className = "center";
+  play.appendChild(link);
 
   if (top) tbody.insertBefore(tr, tbody.firstChild);
   else tbody.appendChild(tr);
 }
 
-function set_processing(processing) {
-  let processing_message = document.querySelector("#processing");
-  processing_message.style.display = processing != null ? "block" : "none";
-  processing_message.innerHTML = processing;
+function to_db(rms) {
+  return Math.max(-60, 20 * Math.log10(rms));
+}
+
+function channel_row(message) {
+  let tr = document.querySelector(`#channel-${message.idx}`);
+  if (tr == null) {
+    tr = document.createElement("tr");
+    tr.id = `channel-${message.idx}`;
+    tr.innerHTML = `
+            <td class="name"></td>
+            <td><meter min="-60" max="3"></meter> <span class="level"></span></td>
+            <td class="noise-floor"></td>
+            <td class="squelch"></td>
+            <td class="status">Idle</td>
+            <td class="center"><button class="listen">▶</button></td>
+        `;
+    let button = tr.querySelector(".listen");
+    button.onclick = () => toggle_live(tr.querySelector(".name").innerText, button);
+    channels.appendChild(tr);
+  }
+
+  tr.querySelector(".name").innerText = message.name;
+  return tr;
+}
+
+function set_status(message, status) {
+  channel_row(message).querySelector(".status").innerText = status;
+}
+
+function set_level(message) {
+  let tr = channel_row(message);
+  let level = to_db(message.rms);
+  tr.querySelector("meter").value = level;
+  tr.querySelector(".level").innerText = level.toFixed(1);
+  tr.querySelector(".noise-floor").innerText = to_db(message.noise_floor).toFixed(1);
+  tr.querySelector(".squelch").innerText = message.open ? "Open" : "Closed";
+}
+
+// Plays a channel's audio as it's received, streamed as 16 bit PCM frames.
+function toggle_live(name, button) {
+  if (players[name]) {
+    players[name]();
+    delete players[name];
+    button.innerText = "▶";
+    return;
+  }
+
+  let audio = new AudioContext({ sampleRate: SAMPLE_RATE });
+  let socket = new WebSocket(`${WS_PROTOCOL}//${location.host}/live?channel=${encodeURIComponent(name)}`);
+  socket.binaryType = "arraybuffer";
+
+  let next = 0;
+  socket.onmessage = (event) => {
+    let samples = new Int16Array(event.data);
+    let buffer = audio.createBuffer(1, samples.length, SAMPLE_RATE);
+    buffer.getChannelData(0).set(Float32Array.from(samples, (x) => x / 32767));
+
+    let source = audio.createBufferSource();
+    source.buffer = buffer;
+    source.connect(audio.destination);
+
+    // Leave a little slack so network jitter doesn't cause gaps
+    next = Math.max(next, audio.currentTime + 0.1);
+    source.start(next);
+    next += buffer.duration;
+  };
+
+  players[name] = () => {
+    socket.close();
+    audio.close();
+  };
+  button.innerText = "■";
+}
+
+function show_alert(message) {
+  let title = `${message.alert} on ${message.name}`;
+  let li = document.createElement("li");
+  li.innerText = `${new Date().toLocaleTimeString()} ${title}: ${message.text}`;
+  li.onclick = () => li.remove();
+  alerts.insertBefore(li, alerts.firstChild);
+
+  if (window.Notification?.permission === "granted")
+    new Notification(title, { body: message.text, requireInteraction: true });
+  if (alert_audio) beep(alert_audio);
+}
+
+// Three short tones, loud enough to notice from across the room
+function beep(audio) {
+  for (let i = 0; i < 3; i++) {
+    let start = audio.currentTime + i * 0.3;
+    let oscillator = audio.createOscillator();
+    oscillator.frequency.value = 880;
+    oscillator.connect(audio.destination);
+    oscillator.start(start);
+    oscillator.stop(start + 0.2);
+  }
 }
 
+const SAMPLE_RATE = 44100;
+const WS_PROTOCOL = location.protocol === "https:" ? "wss:" : "ws:";
+let players = {};
+let channels = document.querySelector("#channels");
 let tbody = document.querySelector("#messages");
+let alerts = document.querySelector("#alerts");
+let alert_audio = null;
+
+// Browsers only allow notifications and sound after the user asks for them
+document.querySelector("#enable-alerts").onclick = (event) => {
+  window.Notification?.requestPermission();
+  alert_audio ??= new AudioContext();
+  alert_audio.resume();
+  event.target.remove();
+};
 
 fetch("/messages")
-  .then((r) => r.json())
+  .then((r) => {
+    if (r.status === 401) location.href = "/login.html";
+    return r.json();
+  })
   .then((messages) => {
     for (let message of messages) add_message(message, false);
   });
 
-let ws = new WebSocket(`ws://${location.host}/events`);
+// Latest tuner gain and device error of each radio, by name
+let gains = {};
+let disconnected = {};
+
+let ws = new WebSocket(`${WS_PROTOCOL}//${location.host}/events`);
 ws.onmessage = (event) => {
   let message = JSON.parse(event.data);
-  if (message.type === "Receiving") set_processing("Receiving...");
-  else if (message.type === "Processing") set_processing("Processing...");
+  if (message.type === "Level") set_level(message);
+  else if (message.type === "Receiving") set_status(message, "Receiving...");
+  else if (message.type === "Processing") set_status(message, "Processing...");
+  else if (message.type === "Discarded") set_status(message, "Idle");
+  else if (message.type === "Alert") show_alert(message);
+  else if (message.type === "TunerGain") {
+    gains[message.radio] = message.gain;
+    document.querySelector("#gain").innerText =
+      "Tuner gain: " +
+      Object.entries(gains)
+        .map(([radio, gain]) => `${radio} ${gain} dB`)
+        .join(", ");
+  } else if (message.type === "Device") {
+    if (message.connected) delete disconnected[message.radio];
+    else disconnected[message.radio] = message.error;
+
+    let device = document.querySelector("#device");
+    device.hidden = Object.keys(disconnected).length === 0;
+    device.innerText = Object.entries(disconnected)
+      .map(([radio, error]) => `Radio ${radio} disconnected, reconnecting... (${error})`)
+      .join("\n");
+  }
   else if (message.type === "Complete") {
-    set_processing(null);
+    set_status(message, "Idle");
     add_message(message, true);
   }
 };
+
+document.querySelector("#logout").onclick = () =>
+  fetch("/logout", { method: "POST" }).then(() => (location.href = "/login.html"));
@@ -0,0 +1,15 @@
+let form = document.querySelector("#login");
+form.onsubmit = async (event) => {
+  event.preventDefault();
+  let response = await fetch("/login", {
+    method: "POST",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify({
+      name: form.name.value,
+      password: form.password.value,
+    }),
+  });
+
+  if (response.ok) location.href = "/";
+  else document.querySelector("#error").innerText = await response.text();
+};
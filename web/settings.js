@@ -0,0 +1,115 @@
+const MIN_DB = -60;
+const MAX_DB = 2.9;
+const WS_PROTOCOL = location.protocol === "https:" ? "wss:" : "ws:";
+
+let tbody = document.querySelector("#channels");
+let error = document.querySelector("#error");
+
+function to_db(rms) {
+  return Math.max(MIN_DB, 20 * Math.log10(rms));
+}
+
+function from_db(db) {
+  return Math.pow(10, db / 20);
+}
+
+function request(method, url, body) {
+  fetch(url, {
+    method,
+    headers: { "Content-Type": "application/json" },
+    body: body && JSON.stringify(body),
+  }).then(async (r) => {
+    if (!r.ok) {
+      error.innerText = await r.text();
+      return;
+    }
+
+    error.innerText = "";
+    show_channels(await r.json());
+  });
+}
+
+function show_channels(channels) {
+  tbody.innerHTML = "";
+  channels.forEach((channel, idx) => {
+    let squelch = to_db(channel.squelch).toFixed(1);
+    let tr = document.createElement("tr");
+    tr.innerHTML = `
+            <td><input type="text" name="name" /></td>
+            <td><input type="number" name="freq" step="0.0125" value="${channel.freq / 1e6}" /></td>
+            <td>
+              <select name="mode">
+                <option value="fm">FM</option>
+                <option value="am">AM</option>
+              </select>
+            </td>
+            <td><input type="text" name="radio" placeholder="First radio" /></td>
+            <td><input type="number" name="gain" step="0.5" value="${channel.gain}" /></td>
+            <td class="squelch">
+              <meter id="level-${idx}" min="${MIN_DB}" max="${MAX_DB}" value="${MIN_DB}"></meter>
+              <input type="range" name="squelch" min="${MIN_DB}" max="${MAX_DB}" step="0.5" value="${squelch}" />
+              <span>Squelch ${squelch} dB, signal <span id="level-text-${idx}">-</span></span>
+            </td>
+            <td>
+              <button name="save">Save</button>
+              <button name="delete">Delete</button>
+            </td>
+        `;
+
+    let field = (name) => tr.querySelector(`[name=${name}]`);
+    field("name").value = channel.name;
+    field("mode").value = channel.mode;
+    field("radio").value = channel.radio ?? "";
+
+    field("squelch").oninput = () =>
+      (tr.querySelector(".squelch > span").firstChild.textContent =
+        `Squelch ${field("squelch").value} dB, signal `);
+    field("squelch").onchange = () =>
+      request("PUT", `/channels/${idx}`, {
+        squelch: from_db(field("squelch").value),
+      });
+    field("save").onclick = () =>
+      request("PUT", `/channels/${idx}`, read_channel(field));
+    field("delete").onclick = () => {
+      if (confirm(`Delete ${channel.name}?`))
+        request("DELETE", `/channels/${idx}`);
+    };
+
+    tbody.appendChild(tr);
+  });
+}
+
+function read_channel(field) {
+  return {
+    name: field("name").value,
+    freq: Math.round(field("freq").value * 1e6),
+    mode: field("mode").value,
+    radio: field("radio").value || null,
+    gain: parseFloat(field("gain").value),
+    squelch: from_db(field("squelch").value),
+  };
+}
+
+document.querySelector("#add").onclick = () => {
+  let row = document.querySelector("#new");
+  request("POST", "/channels", read_channel((name) => row.querySelector(`[name=${name}]`)));
+};
+
+fetch("/channels")
+  .then((r) => {
+    if (r.status === 401) location.href = "/login.html";
+    return r.json();
+  })
+  .then(show_channels);
+
+let ws = new WebSocket(`${WS_PROTOCOL}//${location.host}/events`);
+ws.onmessage = (event) => {
+  let message = JSON.parse(event.data);
+  if (message.type !== "Level") return;
+
+  let level = to_db(message.rms);
+  let meter = document.querySelector(`#level-${message.idx}`);
+  if (meter) meter.value = level;
+  let text = document.querySelector(`#level-text-${message.idx}`);
+  if (text) text.innerText = `${level.toFixed(1)} dB`;
+};
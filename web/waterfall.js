@@ -0,0 +1,96 @@
+const COLOR_SCHEME = [
+  [0x00, 0x00, 0x00],
+  [0x74, 0x29, 0x75],
+  [0xdd, 0x56, 0x2e],
+  [0xfd, 0x97, 0x19],
+  [0xff, 0xd7, 0x6b],
+  [0xff, 0xff, 0xff],
+];
+const LEVEL_MIN_DB = -60;
+const WS_PROTOCOL = location.protocol === "https:" ? "wss:" : "ws:";
+
+let waterfall = document.querySelector("#waterfall");
+let overlay = document.querySelector("#overlay");
+let waterfall_ctx = waterfall.getContext("2d");
+let overlay_ctx = overlay.getContext("2d");
+
+function color(value) {
+  let sections = COLOR_SCHEME.length - 1;
+  let position = Math.min(Math.max(value, 0), 1) * sections;
+  let section = Math.min(Math.floor(position), sections - 1);
+  let t = position - section;
+
+  let [a, b] = [COLOR_SCHEME[section], COLOR_SCHEME[section + 1]];
+  return a.map((x, i) => x + (b[i] - x) * t);
+}
+
+function to_db(rms) {
+  return Math.max(LEVEL_MIN_DB, 20 * Math.log10(rms));
+}
+
+function draw_row(row) {
+  if (waterfall.width !== row.bins.length) {
+    waterfall.width = overlay.width = row.bins.length;
+  }
+
+  let min = parseFloat(document.querySelector("#min").value);
+  let max = parseFloat(document.querySelector("#max").value);
+
+  // Scroll everything down a pixel, then draw the new row at the top
+  waterfall_ctx.drawImage(waterfall, 0, 1);
+  let image = waterfall_ctx.createImageData(row.bins.length, 1);
+  row.bins.forEach((db, x) => {
+    let [r, g, b] = color((db - min) / (max - min));
+    image.data.set([r, g, b, 255], x * 4);
+  });
+  waterfall_ctx.putImageData(image, 0, 0);
+}
+
+function draw_overlay(row) {
+  let { width, height } = overlay;
+  let to_x = (freq) =>
+    ((freq - row.center_freq + row.sample_rate / 2) / row.sample_rate) * width;
+  let to_y = (db) => height - ((db - LEVEL_MIN_DB) / -LEVEL_MIN_DB) * (height - 20);
+
+  overlay_ctx.clearRect(0, 0, width, height);
+  overlay_ctx.font = "12px sans-serif";
+  overlay_ctx.textAlign = "center";
+
+  for (let channel of row.channels) {
+    let x = to_x(channel.freq);
+    let half_width = (row.channel_width / row.sample_rate / 2) * width;
+
+    overlay_ctx.fillStyle = channel.open ? "rgba(0, 200, 0, 0.4)" : "rgba(255, 255, 255, 0.15)";
+    overlay_ctx.fillRect(x - half_width, 0, half_width * 2, height);
+
+    overlay_ctx.fillStyle = channel.open ? "#0c0" : "#888";
+    let level = to_y(to_db(channel.rms));
+    overlay_ctx.fillRect(x - 4, level, 8, height - level);
+
+    overlay_ctx.fillStyle = "white";
+    overlay_ctx.fillRect(x - half_width, to_y(to_db(channel.squelch)), half_width * 2, 1);
+    overlay_ctx.fillText(channel.name, x, 12);
+  }
+
+  document.querySelector("#center").innerText = `Center ${row.center_freq / 1e6} MHz`;
+}
+
+document.querySelector("#radio").onchange = () =>
+  waterfall_ctx.clearRect(0, 0, waterfall.width, waterfall.height);
+
+let ws = new WebSocket(`${WS_PROTOCOL}//${location.host}/spectrum`);
+ws.onclose = () =>
+  fetch("/metrics").then((r) => {
+    if (r.status === 401) location.href = "/login.html";
+  });
+ws.onmessage = (event) => {
+  let row = JSON.parse(event.data);
+
+  // Each radio sends its own rows, so only draw the selected one's
+  let radio = document.querySelector("#radio");
+  if (![...radio.options].some((x) => x.value === row.radio)) radio.add(new Option(row.radio));
+  if (row.radio !== radio.value) return;
+
+  draw_row(row);
+  draw_overlay(row);
+};